@@ -0,0 +1,87 @@
+//! Measure the number of cells drawn per second by the CPU backend,
+//! run using `cargo bench --bench cpu`.
+
+use std::time::{Duration, Instant};
+
+use carbonyl::gfx::Size;
+use carbonyl::output::{Backend, Cell, Damage, GlyphSet};
+
+const GLYPHS: [GlyphSet; 5] = [
+    GlyphSet::Quadrant,
+    GlyphSet::Braille,
+    GlyphSet::Sextant,
+    GlyphSet::Octant,
+    GlyphSet::Block,
+];
+
+/// Generate BGRA8888 pixels resembling a web page: dark text on a light background
+/// over two thirds of its width, and a photo-like gradient over the rest
+fn page(size: Size<usize>) -> Vec<u8> {
+    let (width, height) = (size.width * 4, size.height * 8);
+    let mut pixels = Vec::with_capacity(width * height * 4);
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = if x >= width * 2 / 3 {
+                let (u, v) = (x * 255 / width, y * 255 / height);
+
+                [u as u8, v as u8, ((u + v) / 2) as u8 ^ (x * y / 64) as u8]
+            } else {
+                // Lines of 12 pixels high glyphs with 4 pixels of leading,
+                // and strokes varying between glyphs of 6 pixels wide
+                let (line, glyph) = (y % 16, x / 6);
+                let stroke = (glyph * 7 + y / 16 * 3) % 5;
+                let ink = line < 12 && glyph % 9 != 8 && (x % 6 == stroke || line == stroke * 2);
+
+                if ink {
+                    [32, 32, 32]
+                } else {
+                    [250, 250, 250]
+                }
+            };
+
+            pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+        }
+    }
+
+    pixels
+}
+
+fn main() {
+    let size = Size::new(240, 70);
+    let pixels = page(size);
+    let damage = Damage {
+        top: 0,
+        left: 0,
+        right: size.width,
+        bottom: size.height,
+    };
+    let mut cells = (0..size.width * size.height)
+        .map(|index| {
+            let (x, y) = ((index % size.width) as u32, (index / size.width) as u32);
+
+            (Cell::new(x, y), Cell::new(x, y))
+        })
+        .collect::<Vec<_>>();
+
+    for glyphs in GLYPHS {
+        let mut backend = Backend::Cpu.create(glyphs).unwrap();
+        let start = Instant::now();
+        let mut frames = 0;
+
+        backend.set_size(size);
+
+        while start.elapsed() < Duration::from_secs(1) {
+            backend.draw(&pixels, size * (4, 8), damage, &mut cells);
+            frames += 1;
+        }
+
+        let cells = (frames * size.width * size.height) as f64;
+
+        println!(
+            "{:?}: {:.2}M cells/s",
+            glyphs,
+            cells / start.elapsed().as_secs_f64() / 1e6
+        );
+    }
+}
@@ -12,8 +12,8 @@ fn link_sysroot() {
             "cargo:rustc-link-arg=--sysroot=./chromium/src/build/linux/debian_bullseye_amd64-sysroot"
         );
     } else {
-        println!("cargo:warning={}", "x86_64 debian sysroot provided by chromium was not found!");
-        println!("cargo:warning={}", "carbonyl may fail to link against a proper libc!");
+        println!("cargo:warning=x86_64 debian sysroot provided by chromium was not found!");
+        println!("cargo:warning=carbonyl may fail to link against a proper libc!");
     }
 }
 
@@ -29,8 +29,8 @@ fn link_sysroot() {
             "cargo:rustc-link-arg=--sysroot=./chromium/src/build/linux/debian_bullseye_i386-sysroot"
         );
     } else {
-        println!("cargo:warning={}", "x86 debian sysroot provided by chromium was not found!");
-        println!("cargo:warning={}", "carbonyl may fail to link against a proper libc!");
+        println!("cargo:warning=x86 debian sysroot provided by chromium was not found!");
+        println!("cargo:warning=carbonyl may fail to link against a proper libc!");
     }
 }
 
@@ -42,17 +42,8 @@ fn link_sysroot() {
 fn main() {
     link_sysroot();
 
-    // Link fidelitty shared library for GPU-accelerated terminal rendering
-    println!("cargo:rustc-link-lib=dylib=fidelitty");
-
-    // Search path for libfidelitty.so
-    if let Ok(lib_dir) = std::env::var("FIDELITTY_LIB_DIR") {
-        println!("cargo:rustc-link-search=native={}", lib_dir);
-    } else {
-        println!("cargo:rustc-link-search=native=/usr/local/lib");
-    }
-
-    // Set RPATH so the runtime linker finds libfidelitty next to the binary
+    // libfidelitty is loaded at runtime when available,
+    // set RPATH so the runtime linker finds it next to the binary
     #[cfg(target_os = "linux")]
     println!("cargo:rustc-link-arg=-Wl,-rpath,$ORIGIN");
     #[cfg(target_os = "macos")]
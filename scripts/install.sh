@@ -42,12 +42,13 @@ echo "Using libcarbonyl: $local_lib"
 # Find libfidelitty
 fidelitty_lib_dir="${FIDELITTY_LIB_DIR:-/usr/local/lib}"
 fidelitty_lib="$fidelitty_lib_dir/libfidelitty.so.${FIDELITTY_VERSION}"
-if [ ! -f "$fidelitty_lib" ]; then
-    echo "libfidelitty.so.${FIDELITTY_VERSION} not found in $fidelitty_lib_dir"
-    echo "Install fidelitty ${FIDELITTY_VERSION} first, or set FIDELITTY_LIB_DIR"
-    exit 1
+if [ -f "$fidelitty_lib" ]; then
+    echo "Using libfidelitty: $fidelitty_lib"
+else
+    echo "Warning: libfidelitty.so.${FIDELITTY_VERSION} not found in $fidelitty_lib_dir"
+    echo "Pages will be rendered on the CPU, install fidelitty ${FIDELITTY_VERSION} or set FIDELITTY_LIB_DIR to use the GPU"
+    fidelitty_lib=""
 fi
-echo "Using libfidelitty: $fidelitty_lib"
 
 # Download upstream carbonyl release
 tmpdir=$(mktemp -d)
@@ -80,10 +81,12 @@ sudo cp -a "$src_dir"/* "$INSTALL_DIR/"
 # Swap in our libcarbonyl
 sudo cp "$local_lib" "$INSTALL_DIR/libcarbonyl.so"
 
-# Bundle libfidelitty
-sudo cp "$fidelitty_lib" "$INSTALL_DIR/libfidelitty.so.${FIDELITTY_VERSION}"
-sudo ln -sf "libfidelitty.so.${FIDELITTY_VERSION}" "$INSTALL_DIR/libfidelitty.so.0"
-sudo ln -sf "libfidelitty.so.0" "$INSTALL_DIR/libfidelitty.so"
+# Bundle libfidelitty, loaded at runtime when present
+if [ -n "$fidelitty_lib" ]; then
+    sudo cp "$fidelitty_lib" "$INSTALL_DIR/libfidelitty.so.${FIDELITTY_VERSION}"
+    sudo ln -sf "libfidelitty.so.${FIDELITTY_VERSION}" "$INSTALL_DIR/libfidelitty.so.0"
+    sudo ln -sf "libfidelitty.so.0" "$INSTALL_DIR/libfidelitty.so"
+fi
 
 # Create symlink on PATH
 sudo ln -sf "$INSTALL_DIR/carbonyl" "$BIN_LINK"
@@ -92,5 +95,5 @@ echo ""
 echo "Installed carbonyl to $INSTALL_DIR"
 echo "  upstream: ${REPO} ${CARBONYL_VERSION}"
 echo "  libcarbonyl: $local_lib"
-echo "  libfidelitty: ${FIDELITTY_VERSION}"
+echo "  libfidelitty: ${fidelitty_lib:+${FIDELITTY_VERSION}}${fidelitty_lib:-not installed}"
 echo "  binary: $BIN_LINK -> $INSTALL_DIR/carbonyl"
@@ -1,6 +1,7 @@
 use std::ffi::{CStr, CString};
 use std::io::Write;
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::{mpsc, Mutex};
 use std::{env, io, thread};
 
@@ -8,7 +9,7 @@ use libc::{c_char, c_float, c_int, c_uchar, c_uint, c_void, size_t};
 
 use crate::cli::{CommandLine, CommandLineProgram, EnvVar};
 use crate::gfx::{Cast, Color, Point, Rect, Size};
-use crate::output::{RenderThread, Window};
+use crate::output::{Bitmap, GraphicsProtocol, RenderThread, Window};
 use crate::ui::navigation::NavigationAction;
 use crate::{input, utils::log};
 
@@ -45,6 +46,16 @@ pub struct CText {
     color: CColor,
 }
 
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CKey {
+    /// Unicode codepoint, or code of a functional key
+    code: c_uint,
+    /// Bit set of shift (1), alt (2), control (4) and meta (8)
+    modifiers: c_uint,
+    repeat: bool,
+}
+
 #[repr(C)]
 pub struct RendererBridge {
     cmd: CommandLine,
@@ -55,6 +66,8 @@ pub struct RendererBridge {
 unsafe impl Send for RendererBridge {}
 unsafe impl Sync for RendererBridge {}
 
+/// Pointer to a renderer, passed back by Chromium to the exported functions
+/// as returned by `carbonyl_renderer_create`.
 pub type RendererPtr = *const Mutex<RendererBridge>;
 
 impl<T: Copy> From<CPoint> for Point<T>
@@ -81,6 +94,25 @@ where
         Size::new(value.width, value.height).cast()
     }
 }
+impl From<&input::Key> for CKey {
+    fn from(key: &input::Key) -> Self {
+        let input::KeyModifiers {
+            shift,
+            alt,
+            control,
+            meta,
+        } = key.modifiers;
+
+        CKey {
+            code: key.char as c_uint,
+            modifiers: shift as c_uint
+                | (alt as c_uint) << 1
+                | (control as c_uint) << 2
+                | (meta as c_uint) << 3,
+            repeat: key.event == input::KeyEvent::Repeat,
+        }
+    }
+}
 impl From<CColor> for Color {
     fn from(value: CColor) -> Self {
         Color::new(value.r, value.g, value.b)
@@ -96,7 +128,7 @@ pub struct BrowserDelegate {
     go_back: extern "C" fn(),
     go_forward: extern "C" fn(),
     scroll: extern "C" fn(c_int),
-    key_press: extern "C" fn(c_char),
+    key_press: extern "C" fn(CKey),
     mouse_up: extern "C" fn(c_uint, c_uint),
     mouse_down: extern "C" fn(c_uint, c_uint),
     mouse_move: extern "C" fn(c_uint, c_uint),
@@ -113,7 +145,7 @@ fn main() -> io::Result<Option<i32>> {
         return Ok(None);
     }
 
-    let mut terminal = input::Terminal::setup();
+    let mut terminal = input::Terminal::setup(cmd.graphics);
     let mut command = Command::new(env::current_exe()?);
 
     if !cmd.bitmap {
@@ -170,6 +202,7 @@ pub extern "C" fn carbonyl_renderer_create() -> RendererPtr {
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn carbonyl_renderer_start(bridge: RendererPtr) {
     {
         let bridge = unsafe { bridge.as_ref() };
@@ -182,20 +215,27 @@ pub extern "C" fn carbonyl_renderer_start(bridge: RendererPtr) {
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn carbonyl_renderer_resize(bridge: RendererPtr) {
     let bridge = unsafe { bridge.as_ref() };
     let mut bridge = bridge.unwrap().lock().unwrap();
     let window = bridge.window.update();
-    let cells = window.cells.clone();
+    let cells = window.cells;
+    let cell_size = window.cell_size;
 
     log::debug!("resizing renderer, terminal window: {:?}", window);
 
-    bridge
-        .renderer
-        .render(move |renderer| renderer.set_size(cells));
+    bridge.renderer.render(move |renderer| {
+        if let Some(size) = cell_size {
+            renderer.set_cell_size(size);
+        }
+
+        renderer.set_size(cells)
+    });
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn carbonyl_renderer_push_nav(
     bridge: RendererPtr,
     url: *const c_char,
@@ -211,6 +251,7 @@ pub extern "C" fn carbonyl_renderer_push_nav(
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn carbonyl_renderer_set_title(bridge: RendererPtr, title: *const c_char) {
     let (bridge, title) = unsafe { (bridge.as_ref(), CStr::from_ptr(title)) };
     let (mut bridge, title) = (bridge.unwrap().lock().unwrap(), title.to_owned());
@@ -221,6 +262,7 @@ pub extern "C" fn carbonyl_renderer_set_title(bridge: RendererPtr, title: *const
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn carbonyl_renderer_draw_text(
     bridge: RendererPtr,
     text: *const CText,
@@ -264,6 +306,7 @@ unsafe impl Send for CallbackData {}
 unsafe impl Sync for CallbackData {}
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn carbonyl_renderer_draw_bitmap(
     bridge: RendererPtr,
     pixels: *const c_uchar,
@@ -277,21 +320,19 @@ pub extern "C" fn carbonyl_renderer_draw_bitmap(
     let callback_data = CallbackData(callback_data);
     let mut bridge = bridge.unwrap().lock().unwrap();
 
-    bridge.renderer.render(move |renderer| {
-        renderer.draw_background(
-            pixels,
-            pixels_size.into(),
-            Rect {
-                size: rect.size.into(),
-                origin: rect.origin.into(),
-            },
-        );
-
-        callback(callback_data.as_ptr());
+    bridge.renderer.draw_bitmap(Bitmap {
+        pixels,
+        size: pixels_size.into(),
+        rect: Rect {
+            size: rect.size.into(),
+            origin: rect.origin.into(),
+        },
+        ack: Box::new(move || callback(callback_data.as_ptr())),
     });
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn carbonyl_renderer_get_size(bridge: RendererPtr) -> CSize {
     let bridge = unsafe { bridge.as_ref() };
     let bridge = bridge.unwrap().lock().unwrap();
@@ -301,6 +342,20 @@ pub extern "C" fn carbonyl_renderer_get_size(bridge: RendererPtr) -> CSize {
     bridge.window.browser.into()
 }
 
+/// Exit code of the browser process, once Chromium is done shutting down
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn exit_with_code() {
+    unsafe { libc::_exit(EXIT_CODE.load(Ordering::SeqCst)) }
+}
+
+/// Chromium exits with 0 after a shutdown, set a code used once it returns from its main.
+fn set_exit_code(code: i32) {
+    if code != 0 && EXIT_CODE.swap(code, Ordering::SeqCst) == 0 {
+        unsafe { libc::atexit(exit_with_code) };
+    }
+}
+
 extern "C" fn post_task_handler(callback: *mut c_void) {
     let mut closure = unsafe { Box::from_raw(callback as *mut Box<dyn FnMut()>) };
 
@@ -321,6 +376,7 @@ where
 /// This will block so the calling code should start and own a dedicated thread.
 /// It will panic if there is any error.
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn carbonyl_renderer_listen(bridge: RendererPtr, delegate: *mut BrowserDelegate) {
     let bridge = unsafe { &*bridge };
     let delegate = unsafe { *delegate };
@@ -343,6 +399,12 @@ pub extern "C" fn carbonyl_renderer_listen(bridge: RendererPtr, delegate: *mut B
             }};
         }
 
+        // Quit the browser when the renderer is done, such as after a screenshot
+        bridge.lock().unwrap().renderer.on_exit(move |code| {
+            set_exit_code(code);
+            emit!(shutdown())
+        });
+
         listen(|mut events| {
             bridge.lock().unwrap().renderer.render(move |renderer| {
                 let get_scale = || bridge.lock().unwrap().window.scale;
@@ -358,6 +420,10 @@ pub extern "C" fn carbonyl_renderer_listen(bridge: RendererPtr, delegate: *mut B
                 let dispatch = |action| {
                     match action {
                         NavigationAction::Ignore => (),
+                        // Handled by the renderer
+                        NavigationAction::CycleBackend()
+                        | NavigationAction::Screenshot()
+                        | NavigationAction::Capture() => (),
                         NavigationAction::Forward => return true,
                         NavigationAction::GoBack() => emit!(go_back()),
                         NavigationAction::GoForward() => emit!(go_forward()),
@@ -369,7 +435,7 @@ pub extern "C" fn carbonyl_renderer_listen(bridge: RendererPtr, delegate: *mut B
                         }
                     };
 
-                    return false;
+                    false
                 };
 
                 for event in std::mem::take(&mut events) {
@@ -382,9 +448,11 @@ pub extern "C" fn carbonyl_renderer_listen(bridge: RendererPtr, delegate: *mut B
 
                             emit!(scroll((delta as f32 * scale.height) as c_int))
                         }
+                        // Chromium releases keys right after pressing them
+                        KeyPress { key } if key.event == input::KeyEvent::Release => (),
                         KeyPress { key } => {
                             if dispatch(renderer.keypress(&key).unwrap()) {
-                                emit!(key_press(key.char as c_char))
+                                emit!(key_press((&key).into()))
                             }
                         }
                         MouseUp { col, row } => {
@@ -409,8 +477,22 @@ pub extern "C" fn carbonyl_renderer_listen(bridge: RendererPtr, delegate: *mut B
                             }
                         }
                         Terminal(terminal) => match terminal {
-                            TerminalEvent::Name(name) => log::debug!("terminal name: {name}"),
+                            TerminalEvent::Name(name) => {
+                                log::debug!("terminal name: {name}");
+
+                                renderer.set_terminal_name(&name)
+                            }
                             TerminalEvent::TrueColorSupported => renderer.enable_true_color(),
+                            TerminalEvent::KittyGraphicsSupported => {
+                                renderer.enable_graphics(GraphicsProtocol::Kitty)
+                            }
+                            TerminalEvent::SynchronizedOutputSupported => {
+                                renderer.enable_synchronized_output()
+                            }
+                            TerminalEvent::Color(kind, color) => {
+                                renderer.set_terminal_color(kind, color)
+                            }
+                            TerminalEvent::CellSize(size) => renderer.set_cell_size(size),
                         },
                     }
                 }
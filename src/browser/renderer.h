@@ -11,6 +11,22 @@
 extern "C" {
 
 struct carbonyl_renderer;
+
+enum carbonyl_renderer_key_modifier {
+    CARBONYL_KEY_SHIFT = 1,
+    CARBONYL_KEY_ALT = 2,
+    CARBONYL_KEY_CONTROL = 4,
+    CARBONYL_KEY_META = 8,
+};
+
+// A key press, using the codes of the kitty keyboard protocol for functional keys
+// and the control codes from 0x11 to 0x14 for arrow keys.
+struct carbonyl_renderer_key {
+    uint32_t code;
+    uint32_t modifiers;
+    bool repeat;
+};
+
 struct carbonyl_renderer_browser_delegate {
     void (*shutdown) ();
     void (*refresh) ();
@@ -18,7 +34,7 @@ struct carbonyl_renderer_browser_delegate {
     void (*go_back) ();
     void (*go_forward) ();
     void (*scroll) (int);
-    void (*key_press) (char);
+    void (*key_press) (struct carbonyl_renderer_key);
     void (*mouse_up) (unsigned int, unsigned int);
     void (*mouse_down) (unsigned int, unsigned int);
     void (*mouse_move) (unsigned int, unsigned int);
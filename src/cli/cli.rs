@@ -1,4 +1,8 @@
-use std::{env, ffi::OsStr};
+use std::{env, ffi::OsStr, path::PathBuf};
+
+use crate::output::{
+    Backend, CaptureFrames, ColorDepth, Dither, GlyphSet, GraphicsProtocol, Mirror,
+};
 
 use super::CommandLineProgram;
 
@@ -6,9 +10,21 @@ use super::CommandLineProgram;
 pub struct CommandLine {
     pub args: Vec<String>,
     pub fps: f32,
+    pub min_fps: f32,
     pub zoom: f32,
     pub debug: bool,
     pub bitmap: bool,
+    pub no_split_frames: bool,
+    pub graphics: Option<GraphicsProtocol>,
+    pub glyphs: GlyphSet,
+    pub dither: Dither,
+    pub color: Option<ColorDepth>,
+    pub renderer: Option<Backend>,
+    pub mirror: Option<Mirror>,
+    pub record: Option<PathBuf>,
+    pub screenshot: Option<PathBuf>,
+    pub capture: Option<PathBuf>,
+    pub capture_frames: Option<CaptureFrames>,
     pub program: CommandLineProgram,
     pub shell_mode: bool,
 }
@@ -16,7 +32,9 @@ pub struct CommandLine {
 pub enum EnvVar {
     Debug,
     Bitmap,
+    NoSplitFrames,
     ShellMode,
+    Dither,
 }
 
 impl EnvVar {
@@ -24,7 +42,9 @@ impl EnvVar {
         match self {
             EnvVar::Debug => "CARBONYL_ENV_DEBUG",
             EnvVar::Bitmap => "CARBONYL_ENV_BITMAP",
+            EnvVar::NoSplitFrames => "CARBONYL_ENV_NO_SPLIT_FRAMES",
             EnvVar::ShellMode => "CARBONYL_ENV_SHELL_MODE",
+            EnvVar::Dither => "CARBONYL_ENV_DITHER",
         }
     }
 }
@@ -38,9 +58,21 @@ impl AsRef<OsStr> for EnvVar {
 impl CommandLine {
     pub fn parse() -> CommandLine {
         let mut fps = 60.0;
+        let mut min_fps = 5.0;
         let mut zoom = 1.0;
         let mut debug = false;
         let mut bitmap = false;
+        let mut no_split_frames = false;
+        let mut graphics = None;
+        let mut glyphs = GlyphSet::Quadrant;
+        let mut dither = None;
+        let mut color = None;
+        let mut renderer = None;
+        let mut mirror = None;
+        let mut record: Option<PathBuf> = None;
+        let mut screenshot: Option<PathBuf> = None;
+        let mut capture: Option<PathBuf> = None;
+        let mut capture_frames = None;
         let mut shell_mode = false;
         let mut program = CommandLineProgram::Main;
         let args = env::args().skip(1).collect::<Vec<String>>();
@@ -48,7 +80,7 @@ impl CommandLine {
         for arg in &args {
             let split: Vec<&str> = arg.split("=").collect();
             let default = arg.as_str();
-            let (key, value) = (split.get(0).unwrap_or(&default), split.get(1));
+            let (key, value) = (split.first().unwrap_or(&default), split.get(1));
 
             macro_rules! set {
                 ($var:ident, $enum:ident) => {{
@@ -56,12 +88,28 @@ impl CommandLine {
 
                     env::set_var(EnvVar::$enum, "1");
                 }};
+                ($var:ident = $expr:expr, $enum:ident) => {{
+                    if let Some((Ok(parsed), value)) = value.map(|value| (value.parse(), value)) {
+                        $var = {
+                            let $var = parsed;
+
+                            $expr
+                        };
+
+                        env::set_var(EnvVar::$enum, value);
+                    }
+                }};
             }
 
             macro_rules! set_f32 {
                 ($var:ident = $expr:expr) => {{
                     if let Some(value) = value {
-                        if let Some(value) = value.parse::<f32>().ok() {
+                        // Rates and factors are only meaningful when positive
+                        if let Some(value) = value
+                            .parse::<f32>()
+                            .ok()
+                            .filter(|value| value.is_finite() && *value > 0.0)
+                        {
                             $var = {
                                 let $var = value;
 
@@ -72,11 +120,35 @@ impl CommandLine {
                 }};
             }
 
+            macro_rules! set_parsed {
+                ($var:ident = $expr:expr) => {{
+                    if let Some(Ok(value)) = value.map(|value| value.parse()) {
+                        $var = {
+                            let $var = value;
+
+                            $expr
+                        };
+                    }
+                }};
+            }
+
             match *key {
                 "-f" | "--fps" => set_f32!(fps = fps),
+                "--min-fps" => set_f32!(min_fps = min_fps),
                 "-z" | "--zoom" => set_f32!(zoom = zoom / 100.0),
                 "-d" | "--debug" => set!(debug, Debug),
                 "-b" | "--bitmap" => set!(bitmap, Bitmap),
+                "--no-split-frames" => set!(no_split_frames, NoSplitFrames),
+                "-g" | "--graphics" => set_parsed!(graphics = Some(graphics)),
+                "--glyphs" => set_parsed!(glyphs = glyphs),
+                "--dither" => set!(dither = Some(dither), Dither),
+                "--color" => set_parsed!(color = Some(color)),
+                "--renderer" => set_parsed!(renderer = Some(renderer)),
+                "--mirror" => set_parsed!(mirror = Some(mirror)),
+                "--record" => set_parsed!(record = Some(record)),
+                "--screenshot" => set_parsed!(screenshot = Some(screenshot)),
+                "--capture" => set_parsed!(capture = Some(capture)),
+                "--capture-frames" => set_parsed!(capture_frames = Some(capture_frames)),
 
                 "-h" | "--help" => program = CommandLineProgram::Help,
                 "-v" | "--version" => program = CommandLineProgram::Version,
@@ -92,16 +164,36 @@ impl CommandLine {
             bitmap = true;
         }
 
+        if env::var(EnvVar::NoSplitFrames).is_ok() {
+            no_split_frames = true;
+        }
+
         if env::var(EnvVar::ShellMode).is_ok() {
             shell_mode = true;
         }
 
+        let dither = dither
+            .or_else(|| env::var(EnvVar::Dither).ok()?.parse().ok())
+            .unwrap_or(Dither::None);
+
         CommandLine {
             args,
             fps,
+            min_fps,
             zoom,
             debug,
             bitmap,
+            no_split_frames,
+            graphics,
+            glyphs,
+            dither,
+            color,
+            renderer,
+            mirror,
+            record,
+            screenshot,
+            capture,
+            capture_frames,
             program,
             shell_mode,
         }
@@ -9,6 +9,8 @@ pub struct Color<T: Copy = u8> {
 }
 
 impl Color {
+    /// Read a BGRA8888 pixel
+    #[allow(clippy::should_implement_trait)]
     pub fn from_iter<'a, T>(iter: &mut T) -> Option<Color>
     where
         T: Iterator<Item = &'a u8>,
@@ -21,6 +23,31 @@ impl Color {
     pub fn black() -> Color {
         Color::<u8>::new(0, 0, 0)
     }
+
+    /// Convert the color to the OKLab perceptual color space, storing L, a and b
+    pub fn to_oklab(&self) -> Color<f64> {
+        let linear = self.cast::<f64>().map(|channel| {
+            let channel = channel / 255.0;
+
+            if channel <= 0.04045 {
+                channel / 12.92
+            } else {
+                ((channel + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        let lms = Color::new(
+            linear.dot((0.4122214708, 0.5363325363, 0.0514459929)),
+            linear.dot((0.2119034982, 0.6806995451, 0.1073969566)),
+            linear.dot((0.0883024619, 0.2817188376, 0.6299787005)),
+        )
+        .map(f64::cbrt);
+
+        Color::new(
+            lms.dot((0.2104542553, 0.7936177850, -0.0040720468)),
+            lms.dot((1.9779984951, -2.4285922050, 0.4505937099)),
+            lms.dot((0.0259040371, 0.7827717662, -0.8086757660)),
+        )
+    }
 }
 
 impl_vector_overload!(Color r g b);
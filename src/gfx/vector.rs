@@ -19,6 +19,9 @@ pub trait Cast<T> {
 }
 
 pub trait ToIntUnchecked<T> {
+    /// # Safety
+    ///
+    /// See [`f32::to_int_unchecked`].
     unsafe fn to_int_unchecked(self) -> T;
 }
 
@@ -155,7 +158,7 @@ macro_rules! impl_vector_overload {
             }
         }
 
-        crate::impl_vector_traits!($struct Vector2);
+        $crate::impl_vector_traits!($struct Vector2);
     );
     ($struct:ident $x:ident $y:ident $z:ident) => (
         impl<T: Copy> $struct<T> {
@@ -237,7 +240,7 @@ macro_rules! impl_vector_overload {
             }
         }
 
-        crate::impl_vector_traits!($struct Vector3);
+        $crate::impl_vector_traits!($struct Vector3);
     );
 }
 
@@ -291,26 +294,26 @@ macro_rules! impl_vector_traits {
             }
         }
 
-        crate::impl_vector_traits!($struct $vector i8);
-        crate::impl_vector_traits!($struct $vector u8);
-        crate::impl_vector_traits!($struct $vector i16);
-        crate::impl_vector_traits!($struct $vector u16);
-        crate::impl_vector_traits!($struct $vector i32);
-        crate::impl_vector_traits!($struct $vector u32);
-        crate::impl_vector_traits!($struct $vector i64);
-        crate::impl_vector_traits!($struct $vector u64);
-        crate::impl_vector_traits!($struct $vector isize);
-        crate::impl_vector_traits!($struct $vector usize);
-        crate::impl_vector_traits!($struct $vector f32 float);
-        crate::impl_vector_traits!($struct $vector f64 float);
-
-        crate::impl_vector_traits!($struct $vector Add add);
-        crate::impl_vector_traits!($struct $vector Sub sub);
-        crate::impl_vector_traits!($struct $vector Mul mul);
-        crate::impl_vector_traits!($struct $vector Div div);
-        crate::impl_vector_traits!($struct $vector BitOr bitor);
-        crate::impl_vector_traits!($struct $vector BitXor bitxor);
-        crate::impl_vector_traits!($struct $vector BitAnd bitand);
+        $crate::impl_vector_traits!($struct $vector i8);
+        $crate::impl_vector_traits!($struct $vector u8);
+        $crate::impl_vector_traits!($struct $vector i16);
+        $crate::impl_vector_traits!($struct $vector u16);
+        $crate::impl_vector_traits!($struct $vector i32);
+        $crate::impl_vector_traits!($struct $vector u32);
+        $crate::impl_vector_traits!($struct $vector i64);
+        $crate::impl_vector_traits!($struct $vector u64);
+        $crate::impl_vector_traits!($struct $vector isize);
+        $crate::impl_vector_traits!($struct $vector usize);
+        $crate::impl_vector_traits!($struct $vector f32 float);
+        $crate::impl_vector_traits!($struct $vector f64 float);
+
+        $crate::impl_vector_traits!($struct $vector Add add);
+        $crate::impl_vector_traits!($struct $vector Sub sub);
+        $crate::impl_vector_traits!($struct $vector Mul mul);
+        $crate::impl_vector_traits!($struct $vector Div div);
+        $crate::impl_vector_traits!($struct $vector BitOr bitor);
+        $crate::impl_vector_traits!($struct $vector BitXor bitxor);
+        $crate::impl_vector_traits!($struct $vector BitAnd bitand);
     };
     ($struct:ident $vector:ident $type:ident) => (
         impl $struct<$type> {
@@ -326,6 +329,9 @@ macro_rules! impl_vector_traits {
     );
     ($struct:ident $vector:ident $type:ident float) => (
         impl $struct<$type> {
+            /// # Safety
+            ///
+            /// See [`f32::to_int_unchecked`].
             pub unsafe fn to_int_unchecked<U>(&self) -> $struct<U>
             where
                 $type: super::ToIntUnchecked<U>,
@@ -1,13 +1,19 @@
+mod apc;
 mod dcs;
 mod keyboard;
 mod listen;
+mod mode;
 mod mouse;
+mod osc;
 mod parser;
 mod tty;
 
+pub use apc::*;
 pub use dcs::*;
 pub use keyboard::*;
 pub use listen::*;
+pub use mode::*;
 pub use mouse::*;
+pub use osc::*;
 pub use parser::*;
 pub use tty::*;
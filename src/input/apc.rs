@@ -0,0 +1,5 @@
+mod graphics;
+mod parser;
+
+pub use graphics::GRAPHICS_QUERY_ID;
+pub use parser::*;
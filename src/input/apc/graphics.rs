@@ -0,0 +1,73 @@
+use crate::{
+    control_flow,
+    input::{Event, ParseControlFlow, TerminalEvent},
+};
+
+/// Image identifier used to query support for the kitty graphics protocol
+pub const GRAPHICS_QUERY_ID: &str = "31";
+
+#[derive(Default, Clone)]
+enum Sequence {
+    #[default]
+    Keys,
+    Message,
+    Terminator,
+}
+
+/// Parse responses from the kitty graphics protocol, such as `ESC _Gi=31;OK ESC \`
+#[derive(Default, Clone)]
+pub struct GraphicsParser {
+    sequence: Sequence,
+    keys: Vec<u8>,
+    message: Vec<u8>,
+}
+
+impl GraphicsParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, key: u8) -> ParseControlFlow {
+        use Sequence::*;
+
+        self.sequence = match self.sequence {
+            Keys => match key {
+                0x1b => Terminator,
+                b';' => Message,
+                key => self.push_char(key),
+            },
+            Message => match key {
+                0x1b => Terminator,
+                key => self.push_char(key),
+            },
+            Terminator => control_flow!(break self.parse_event(key))?,
+        };
+
+        control_flow!(continue)
+    }
+
+    fn push_char(&mut self, key: u8) -> Sequence {
+        match self.sequence {
+            Sequence::Keys => self.keys.push(key),
+            Sequence::Message => self.message.push(key),
+            _ => (),
+        }
+
+        self.sequence.clone()
+    }
+
+    fn parse_event(&self, key: u8) -> Option<Event> {
+        if key != b'\\' || self.message != b"OK" {
+            return None;
+        }
+
+        let keys = std::str::from_utf8(&self.keys).ok()?;
+        let id = keys.split(',').find_map(|pair| pair.strip_prefix("i="))?;
+
+        if id == GRAPHICS_QUERY_ID {
+            Some(Event::Terminal(TerminalEvent::KittyGraphicsSupported))
+        } else {
+            None
+        }
+    }
+}
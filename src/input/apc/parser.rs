@@ -0,0 +1,68 @@
+use crate::{control_flow, input::ParseControlFlow};
+
+use super::graphics::*;
+
+#[derive(Default, Clone)]
+enum Sequence {
+    #[default]
+    Code,
+    Graphics(GraphicsParser),
+    /// Unknown sequence, ignored until its string terminator
+    Ignore,
+    Terminator,
+}
+
+#[derive(Default, Clone)]
+pub struct ApplicationProgram {
+    sequence: Sequence,
+}
+
+impl ApplicationProgram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, key: u8) -> ParseControlFlow {
+        use Sequence::*;
+
+        self.sequence = match self.sequence {
+            Code => match key {
+                b'G' => Graphics(GraphicsParser::new()),
+                0x1b => Terminator,
+                _ => Ignore,
+            },
+            Graphics(ref mut graphics) => return graphics.parse(key),
+            Ignore => match key {
+                0x1b => Terminator,
+                _ => Ignore,
+            },
+            Terminator => control_flow!(break)?,
+        };
+
+        control_flow!(continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::{Event, Parser, TerminalEvent};
+
+    fn parse(input: &[u8]) -> Vec<Event> {
+        Parser::awaiting_replies().parse(input)
+    }
+
+    #[test]
+    fn parses_graphics_support() {
+        assert!(matches!(
+            parse(b"\x1b_Gi=31;OK\x1b\\")[..],
+            [Event::Terminal(TerminalEvent::KittyGraphicsSupported)]
+        ));
+    }
+
+    #[test]
+    fn ignores_graphics_errors() {
+        assert!(parse(b"\x1b_Gi=31;ENOTSUPPORTED:graphics not supported\x1b\\").is_empty());
+        // Replies to other images
+        assert!(parse(b"\x1b_Gi=7;OK\x1b\\").is_empty());
+    }
+}
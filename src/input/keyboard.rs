@@ -1,18 +1,45 @@
-use crate::control_flow;
+use crate::{control_flow, gfx::Size};
 
-use super::{Event, ParseControlFlow};
+use super::{Event, ParseControlFlow, TerminalEvent};
 
+/// Progressive enhancements of the kitty keyboard protocol requested to the terminal:
+/// disambiguated escape codes (1) and repeat and release events (2).
+pub const KEYBOARD_FLAGS: u32 = 0b11;
+
+/// Key codes used by the kitty keyboard protocol for functional keys
+const INSERT: u32 = 57348;
+const DELETE: u32 = 57349;
+const LEFT: u32 = 57350;
+const RIGHT: u32 = 57351;
+const UP: u32 = 57352;
+const DOWN: u32 = 57353;
+const PAGE_UP: u32 = 57354;
+const PAGE_DOWN: u32 = 57355;
+const HOME: u32 = 57356;
+const END: u32 = 57357;
+const F1: u32 = 57364;
+
+/// Parses key sequences, either in the xterm `CSI 1;<modifiers><key>` form,
+/// or in the kitty keyboard protocol `CSI <code>;<modifiers>:<event>u` form.
+///
+/// Also parses the cell size reported in pixels in the `CSI 6;<height>;<width>t` form.
 pub struct Keyboard {
-    state: State,
+    /// Parameters of the sequence, preceding its final byte
+    params: Vec<u8>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Key {
-    pub char: u8,
+    /// Unicode codepoint of the key.
+    ///
+    /// Arrow keys use the control codes from 0x11 to 0x14,
+    /// other functional keys use the private use codes of the kitty keyboard protocol.
+    pub char: char,
     pub modifiers: KeyModifiers,
+    pub event: KeyEvent,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct KeyModifiers {
     pub alt: bool,
     pub meta: bool,
@@ -20,20 +47,48 @@ pub struct KeyModifiers {
     pub control: bool,
 }
 
-enum State {
-    Separator,
-    Modifier(u8),
+/// Type of a key event, terminals without the kitty keyboard protocol only report presses.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum KeyEvent {
+    #[default]
+    Press,
+    Repeat,
+    Release,
 }
 
 impl Keyboard {
-    pub fn new() -> Self {
+    /// Start parsing a sequence from the first byte of its parameters
+    pub fn new(param: u8) -> Self {
         Self {
-            state: State::Separator,
+            params: vec![param],
         }
     }
-    pub fn key(key: u8, modifiers: u8) -> Option<Event> {
-        let modifiers = KeyModifiers::parse(modifiers);
-        let char = match key {
+
+    /// Get the key of a sequence from its parameters and final byte
+    pub fn key(params: &[u8], code: u8) -> Option<Event> {
+        let params = std::str::from_utf8(params).ok()?;
+        // Parameters are separated by semicolons, their fields by colons
+        let param = |index: usize, field: usize| -> Option<u32> {
+            let param = params.split(';').nth(index)?;
+
+            param.split(':').nth(field)?.parse().ok()
+        };
+        let number = param(0, 0).unwrap_or(1);
+
+        if code == b't' && number == 6 {
+            let size = Size::new(param(2, 0)?, param(1, 0)?);
+
+            return Some(Event::Terminal(TerminalEvent::CellSize(size)));
+        }
+
+        let char = match code {
+            b'u' => match number {
+                UP => 0x11,
+                DOWN => 0x12,
+                RIGHT => 0x13,
+                LEFT => 0x14,
+                number => number,
+            },
             // Up
             b'A' => 0x11,
             // Down
@@ -42,66 +97,211 @@ impl Keyboard {
             b'C' => 0x13,
             // Left
             b'D' => 0x14,
+            b'H' => HOME,
+            b'F' => END,
+            b'P' => F1,
+            b'Q' => F1 + 1,
+            b'S' => F1 + 3,
+            b'~' => match number {
+                2 => INSERT,
+                3 => DELETE,
+                5 => PAGE_UP,
+                6 => PAGE_DOWN,
+                1 | 7 => HOME,
+                4 | 8 => END,
+                // F1 to F5, F6 to F10, F11 and F12
+                11..=15 => F1 + number - 11,
+                17..=21 => F1 + number - 12,
+                23 | 24 => F1 + number - 13,
+                _ => return None,
+            },
             _ => return None,
         };
+        let key = Key {
+            char: char::from_u32(char)?,
+            modifiers: KeyModifiers::parse(param(1, 0).unwrap_or(1)),
+            event: match param(1, 1) {
+                Some(2) => KeyEvent::Repeat,
+                Some(3) => KeyEvent::Release,
+                _ => KeyEvent::Press,
+            },
+        };
+
+        // Ctrl+C is reported as a key instead of 0x03 with the kitty keyboard protocol
+        if key.char == 'c' && key.modifiers.control && key.event == KeyEvent::Press {
+            return Some(Event::Exit);
+        }
 
-        Some(Event::KeyPress {
-            key: Key { char, modifiers },
-        })
+        Some(Event::KeyPress { key })
     }
 
     pub fn parse(&mut self, key: u8) -> ParseControlFlow {
-        self.state = match self.state {
-            State::Separator => match key {
-                b';' => State::Modifier(0),
-                _ => control_flow!(break)?,
-            },
-            State::Modifier(code) => match key {
-                b'0'..=b'9' => State::Modifier(code * 10 + key - b'0'),
-                key => control_flow!(break Self::key(key, code))?,
-            },
-        };
+        match key {
+            b'0'..=b'9' | b';' | b':' => control_flow!(self.params.push(key); continue),
+            0x40..=0x7e => control_flow!(break Self::key(&self.params, key)),
+            _ => control_flow!(break),
+        }
+    }
+}
+
+impl Key {
+    pub const UP: char = '\x11';
+    pub const DOWN: char = '\x12';
+    pub const RIGHT: char = '\x13';
+    pub const LEFT: char = '\x14';
 
-        control_flow!(continue)
+    /// Whether the key inputs text, as opposed to control and functional keys
+    /// and shortcuts, which the kitty keyboard protocol reports as letters with modifiers
+    pub fn is_text(&self) -> bool {
+        let functional = (57344..=63743).contains(&(self.char as u32));
+        let KeyModifiers {
+            alt, meta, control, ..
+        } = self.modifiers;
+
+        !self.char.is_control() && !functional && !alt && !meta && !control
     }
 }
 
-impl From<u8> for Key {
-    fn from(char: u8) -> Self {
+impl From<char> for Key {
+    fn from(char: char) -> Self {
         Self {
             char,
             modifiers: KeyModifiers::default(),
+            event: KeyEvent::Press,
         }
     }
 }
 
+impl From<u8> for Key {
+    fn from(char: u8) -> Self {
+        char::from(char).into()
+    }
+}
+
 impl KeyModifiers {
-    pub fn parse(key: u8) -> Self {
-        let (alt, meta, shift, control) = (0b1000, 0b0100, 0b0010, 0b0001);
-        let mask = match key {
-            2 => shift,
-            3 => alt,
-            4 => shift | alt,
-            5 => control,
-            6 => shift | control,
-            7 => alt | control,
-            8 => shift | alt | control,
-            9 => meta,
-            10 => meta | shift,
-            11 => meta | alt,
-            12 => meta | alt | shift,
-            13 => meta | control,
-            14 => meta | control | shift,
-            15 => meta | control | alt,
-            16 => meta | control | alt | shift,
-            _ => 0,
-        };
+    /// Parse modifiers encoded as one plus a bit set of shift (1), alt (2), control (4),
+    /// super (8) and meta (32), super being the command key reported as meta on macOS
+    pub fn parse(key: u32) -> Self {
+        let mask = key.saturating_sub(1);
 
         KeyModifiers {
-            alt: alt & mask != 0,
-            meta: meta & mask != 0,
-            shift: shift & mask != 0,
-            control: control & mask != 0,
+            alt: mask & 0b10 != 0,
+            meta: mask & 0b101000 != 0,
+            shift: mask & 0b1 != 0,
+            control: mask & 0b100 != 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Parser, TerminalEvent};
+    use super::*;
+
+    fn keys(input: &[u8]) -> Vec<Key> {
+        Parser::new()
+            .parse(input)
+            .into_iter()
+            .map(|event| match event {
+                Event::KeyPress { key } => key,
+                event => panic!("unexpected event: {event:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_legacy_keys() {
+        let keys = keys(b"a\x1b[A\x1b[1;3D\x1b[3~\x7f");
+
+        assert_eq!(keys[0], Key::from('a'));
+        assert_eq!(keys[1], Key::from(Key::UP));
+        assert_eq!(keys[2].char, Key::LEFT);
+        assert!(keys[2].modifiers.alt);
+        assert_eq!(keys[3].char as u32, DELETE);
+        assert_eq!(keys[4], Key::from(0x7f_u8));
+    }
+
+    #[test]
+    fn parses_kitty_keys() {
+        let keys = keys("é\x1b[97;5u\x1b[1;1:2B\x1b[27u\x1b[233;2:3u\x1b[15;9~".as_bytes());
+
+        assert_eq!(keys[0], Key::from('é'));
+        assert_eq!(keys[1].char, 'a');
+        assert!(keys[1].modifiers.control);
+        assert_eq!(keys[2].char, Key::DOWN);
+        assert_eq!(keys[2].event, KeyEvent::Repeat);
+        assert_eq!(keys[3], Key::from('\x1b'));
+        assert_eq!(keys[4].char, 'é');
+        assert!(keys[4].modifiers.shift);
+        assert_eq!(keys[4].event, KeyEvent::Release);
+        assert_eq!(keys[5].char as u32, F1 + 4);
+        assert!(keys[5].modifiers.meta);
+    }
+
+    #[test]
+    fn excludes_shortcuts_from_text() {
+        let keys = keys(b"\x1b[108;5u\x1b[102;3u\x1b[102;9u\x1b[65;2u");
+
+        assert!(!keys[0].is_text());
+        assert!(!keys[1].is_text());
+        assert!(!keys[2].is_text());
+        assert!(keys[3].is_text());
+    }
+
+    #[test]
+    fn skips_unknown_application_program_commands() {
+        let events = Parser::awaiting_replies().parse(b"\x1b_Xabc\x1b\\a");
+
+        assert!(matches!(&events[..], [Event::KeyPress { key }] if key.char == 'a'));
+    }
+
+    #[test]
+    fn parses_alt_keys_without_pending_replies() {
+        let alt = |char| [Key::from(0x1b_u8), Key::from(char)];
+
+        assert_eq!(
+            keys(b"\x1b]\x1b_\x1bP"),
+            [alt(']'), alt('_'), alt('P')].concat()
+        );
+
+        let mut parser = Parser::awaiting_replies();
+
+        // Replies end with the device attributes
+        assert_eq!(parser.parse(b"\x1b[?62;22c\x1b]a").len(), 3);
+    }
+
+    #[test]
+    fn parses_late_replies() {
+        let mut parser = Parser::awaiting_replies();
+
+        assert_eq!(parser.parse(b"a").len(), 1);
+
+        // Replies used to be expected within a second
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+
+        assert!(matches!(
+            parser.parse(b"\x1b]11;rgb:0/0/0\x07\x1b[?62;22c")[..],
+            [Event::Terminal(TerminalEvent::Color(..))]
+        ));
+    }
+
+    #[test]
+    fn parses_cell_sizes() {
+        assert!(matches!(
+            Parser::awaiting_replies().parse(b"\x1b[6;17;9t")[..],
+            [Event::Terminal(TerminalEvent::CellSize(Size {
+                width: 9,
+                height: 17
+            }))]
+        ));
+    }
+
+    #[test]
+    fn exits_on_ctrl_c() {
+        assert!(matches!(
+            Parser::new().parse(b"\x1b[99;5u")[..],
+            [Event::Exit]
+        ));
+        assert!(keys(b"\x1b[99;5:3u")[0].modifiers.control);
+    }
+}
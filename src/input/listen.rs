@@ -10,7 +10,7 @@ where
 {
     let mut buf = [0u8; 1024];
     let mut stdin = io::stdin();
-    let mut parser = Parser::new();
+    let mut parser = Parser::awaiting_replies();
 
     loop {
         // Wait for some input
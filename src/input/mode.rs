@@ -0,0 +1,69 @@
+use crate::control_flow;
+
+use super::{Event, ParseControlFlow, TerminalEvent};
+
+/// Private mode used to synchronize output updates
+pub const SYNCHRONIZED_OUTPUT_MODE: u32 = 2026;
+
+/// Parse DECRQM replies reporting a private mode, such as `ESC [ ? 2026 ; 2 $ y`
+#[derive(Default, Clone)]
+pub struct ModeReport {
+    buf: Vec<u8>,
+}
+
+impl ModeReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, key: u8) -> ParseControlFlow {
+        match key {
+            b'y' => control_flow!(break self.get()),
+            // Other final bytes, such as device attributes
+            0x40..=0x7e => control_flow!(break),
+            key => control_flow!(self.buf.push(key); continue),
+        }
+    }
+
+    fn get(&self) -> Option<Event> {
+        let str = std::str::from_utf8(&self.buf).ok()?;
+        let (mode, value) = str.strip_suffix('$')?.split_once(';')?;
+
+        match (mode.parse().ok()?, value) {
+            // The mode is supported if it is either set, reset or permanently set
+            (SYNCHRONIZED_OUTPUT_MODE, "1" | "2" | "3") => {
+                Some(Event::Terminal(TerminalEvent::SynchronizedOutputSupported))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Parser;
+    use super::*;
+
+    fn supported(input: &[u8]) -> bool {
+        match Parser::awaiting_replies().parse(input)[..] {
+            [Event::Terminal(TerminalEvent::SynchronizedOutputSupported)] => true,
+            [] => false,
+            ref events => panic!("unexpected events: {events:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_synchronized_output_reports() {
+        // Not recognized, set, reset, permanently set and permanently reset
+        assert!(!supported(b"\x1b[?2026;0$y"));
+        assert!(supported(b"\x1b[?2026;1$y"));
+        assert!(supported(b"\x1b[?2026;2$y"));
+        assert!(supported(b"\x1b[?2026;3$y"));
+        assert!(!supported(b"\x1b[?2026;4$y"));
+    }
+
+    #[test]
+    fn ignores_other_modes() {
+        assert!(!supported(b"\x1b[?2004;1$y"));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{control_flow, gfx::Color};
+
+use super::{Event, ParseControlFlow, TerminalEvent};
+
+/// Maximum length of an operating system command we care about
+const MAX_LENGTH: usize = 64;
+
+/// A color reported by the terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TerminalColor {
+    /// Color of the palette at a given index
+    Palette(u8),
+    /// Default foreground color
+    Foreground,
+    /// Default background color
+    Background,
+}
+
+/// Parse replies to color queries, such as `ESC ] 4 ; 1 ; rgb:cdcd/0000/0000 ESC \`
+#[derive(Default, Clone)]
+pub struct OperatingSystem {
+    buf: Vec<u8>,
+    escape: bool,
+}
+
+impl OperatingSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, key: u8) -> ParseControlFlow {
+        match key {
+            // Terminated by BEL or ST
+            0x07 => control_flow!(break self.get()),
+            b'\\' if self.escape => control_flow!(break self.get()),
+            _ if self.escape => control_flow!(break),
+            0x1b => control_flow!(self.escape = true; continue),
+            key if self.buf.len() < MAX_LENGTH => control_flow!(self.buf.push(key); continue),
+            _ => control_flow!(continue),
+        }
+    }
+
+    fn get(&self) -> Option<Event> {
+        let str = std::str::from_utf8(&self.buf).ok()?;
+        let mut params = str.split(';');
+        let kind = match params.next()? {
+            "4" => TerminalColor::Palette(params.next()?.parse().ok()?),
+            "10" => TerminalColor::Foreground,
+            "11" => TerminalColor::Background,
+            _ => return None,
+        };
+        let color = parse_color(params.next()?)?;
+
+        Some(Event::Terminal(TerminalEvent::Color(kind, color)))
+    }
+}
+
+/// Parse a color in the `rgb:RRRR/GGGG/BBBB` format, using 1 to 4 hex digits per channel
+fn parse_color(str: &str) -> Option<Color> {
+    let mut channels = str.strip_prefix("rgb:")?.split('/').map(|channel| {
+        if !(1..=4).contains(&channel.len()) {
+            return None;
+        }
+
+        let value = u32::from_str_radix(channel, 16).ok()?;
+        let max = (1 << (channel.len() * 4)) - 1;
+
+        Some((value * 255 / max) as u8)
+    });
+
+    Some(Color::new(
+        channels.next()??,
+        channels.next()??,
+        channels.next()??,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Parser;
+    use super::*;
+
+    fn colors(input: &[u8]) -> Vec<(TerminalColor, Color)> {
+        Parser::awaiting_replies()
+            .parse(input)
+            .into_iter()
+            .map(|event| match event {
+                Event::Terminal(TerminalEvent::Color(kind, color)) => (kind, color),
+                event => panic!("unexpected event: {event:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_color_replies() {
+        assert_eq!(
+            colors(
+                b"\x1b]4;1;rgb:cdcd/0000/0000\x1b\\\x1b]10;rgb:ff/80/00\x07\x1b]11;rgb:0/0/0\x1b\\"
+            ),
+            [
+                (TerminalColor::Palette(1), Color::new(205, 0, 0)),
+                (TerminalColor::Foreground, Color::new(255, 128, 0)),
+                (TerminalColor::Background, Color::black()),
+            ]
+        );
+    }
+
+    #[test]
+    fn scales_channels_by_their_digits() {
+        assert_eq!(parse_color("rgb:f/8/0"), Some(Color::new(255, 136, 0)));
+        assert_eq!(parse_color("rgb:ff/88/00"), Some(Color::new(255, 136, 0)));
+        assert_eq!(
+            parse_color("rgb:fff/800/000"),
+            Some(Color::new(255, 127, 0))
+        );
+        assert_eq!(
+            parse_color("rgb:ffff/8000/0000"),
+            Some(Color::new(255, 127, 0))
+        );
+        assert_eq!(parse_color("rgb:fffff/0/0"), None);
+        assert_eq!(parse_color("rgb:/0/0"), None);
+        assert_eq!(parse_color("rgb:ff/00"), None);
+        assert_eq!(parse_color("rgba:ff/00/00/ff"), None);
+    }
+
+    #[test]
+    fn ignores_other_commands() {
+        assert!(colors(b"\x1b]12;rgb:ff/ff/ff\x07\x1b]4;1;?\x1b\\").is_empty());
+    }
+}
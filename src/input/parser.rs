@@ -1,28 +1,47 @@
 use std::ops::ControlFlow;
 
-use crate::input::*;
+use crate::{
+    gfx::{Color, Size},
+    input::*,
+};
 
 #[derive(Default)]
 pub struct Parser {
     events: Vec<Event>,
     sequence: Sequence,
+    /// Whether replies to terminal queries are pending, string sequences being parsed
+    /// only until then so that Alt+P, Alt+] and Alt+_ are reported as keys otherwise.
+    ///
+    /// Replies can take any time to arrive over slow links, they are pending
+    /// until the device attributes requested last are reported.
+    replies: bool,
 }
 
 #[derive(Default)]
 enum Sequence {
     #[default]
     Char,
+    /// Bytes of an incomplete UTF-8 character
+    Utf8(Vec<u8>),
     Escape,
     Control,
     Mouse(Mouse),
     Keyboard(Keyboard),
+    ModeReport(ModeReport),
     DeviceControl(DeviceControl),
+    OperatingSystem(OperatingSystem),
+    ApplicationProgram(ApplicationProgram),
 }
 
 #[derive(Clone, Debug)]
 pub enum TerminalEvent {
     Name(String),
     TrueColorSupported,
+    KittyGraphicsSupported,
+    SynchronizedOutputSupported,
+    Color(TerminalColor, Color),
+    /// Size of a cell in pixels
+    CellSize(Size),
 }
 
 #[derive(Clone, Debug)]
@@ -43,8 +62,18 @@ impl Parser {
         Self::default()
     }
 
+    /// Create a parser expecting replies to the queries sent by `Terminal::setup`,
+    /// until the device attributes requested last are reported.
+    pub fn awaiting_replies() -> Parser {
+        Self {
+            replies: true,
+            ..Self::default()
+        }
+    }
+
     pub fn parse(&mut self, input: &[u8]) -> Vec<Event> {
         let mut sequence = std::mem::take(&mut self.sequence);
+        let mut replies = self.replies;
 
         macro_rules! emit {
             ($event:expr) => {{
@@ -78,11 +107,27 @@ impl Parser {
                 Sequence::Char => match key {
                     0x1b => Sequence::Escape,
                     0x03 => emit!(Event::Exit),
+                    0xc0..=0xf7 => Sequence::Utf8(vec![key]),
                     key => emit!(Event::KeyPress { key: key.into() }),
                 },
+                Sequence::Utf8(mut bytes) => {
+                    bytes.push(key);
+
+                    match std::str::from_utf8(&bytes) {
+                        Ok(text) => match text.chars().next() {
+                            Some(char) => emit!(Event::KeyPress { key: char.into() }),
+                            None => Sequence::Char,
+                        },
+                        // Wait for the next bytes
+                        Err(error) if error.error_len().is_none() => Sequence::Utf8(bytes),
+                        Err(_) => Sequence::Char,
+                    }
+                }
                 Sequence::Escape => match key {
                     b'[' => Sequence::Control,
-                    b'P' => Sequence::DeviceControl(DeviceControl::new()),
+                    b'P' if replies => Sequence::DeviceControl(DeviceControl::new()),
+                    b']' if replies => Sequence::OperatingSystem(OperatingSystem::new()),
+                    b'_' if replies => Sequence::ApplicationProgram(ApplicationProgram::new()),
                     0x1b => emit!(Event::KeyPress { key: 0x1b.into() }; continue),
                     key => {
                         emit!(Event::KeyPress { key: 0x1b.into() });
@@ -91,12 +136,24 @@ impl Parser {
                 },
                 Sequence::Control => match key {
                     b'<' => Sequence::Mouse(Mouse::new()),
-                    b'1' => Sequence::Keyboard(Keyboard::new()),
-                    key => emit!(Keyboard::key(key, 0)),
+                    b'0'..=b'9' => Sequence::Keyboard(Keyboard::new(key)),
+                    b'?' => Sequence::ModeReport(ModeReport::new()),
+                    key => emit!(Keyboard::key(&[], key)),
                 },
                 Sequence::Mouse(ref mut mouse) => parse!(mouse, key),
                 Sequence::Keyboard(ref mut keyboard) => parse!(keyboard, key),
+                Sequence::ModeReport(ref mut mode) => {
+                    // Device attributes are reported after the replies to every other query
+                    if key == b'c' {
+                        self.replies = false;
+                        replies = false;
+                    }
+
+                    parse!(mode, key)
+                }
                 Sequence::DeviceControl(ref mut dcs) => parse!(dcs, key),
+                Sequence::OperatingSystem(ref mut osc) => parse!(osc, key),
+                Sequence::ApplicationProgram(ref mut apc) => parse!(apc, key),
             }
         }
 
@@ -5,11 +5,15 @@ use std::mem::MaybeUninit;
 use std::os::fd::RawFd;
 use std::os::unix::prelude::AsRawFd;
 
+use crate::output::GraphicsProtocol;
 use crate::utils::log;
 
+use super::{GRAPHICS_QUERY_ID, KEYBOARD_FLAGS, SYNCHRONIZED_OUTPUT_MODE};
+
 pub struct Terminal {
     settings: Option<TerminalSettings>,
     alt_screen: bool,
+    graphics: Option<GraphicsProtocol>,
 }
 
 impl Drop for Terminal {
@@ -19,9 +23,9 @@ impl Drop for Terminal {
 }
 
 impl Terminal {
-    /// Setup the input stream to operate in raw mode.
-    /// Returns an object that'll revert terminal settings.
-    pub fn setup() -> Self {
+    /// Setup the input stream to operate in raw mode, `graphics` being the protocol
+    /// selected to draw pages. Returns an object that'll revert terminal settings.
+    pub fn setup(graphics: Option<GraphicsProtocol>) -> Self {
         Self {
             settings: match TerminalSettings::open_raw() {
                 Ok(settings) => Some(settings),
@@ -31,13 +35,14 @@ impl Terminal {
                     None
                 }
             },
-            alt_screen: if let Err(error) = TTY::enter_alt_screen() {
+            alt_screen: if let Err(error) = Tty::enter_alt_screen(graphics) {
                 log::error!("Failed to enter alternative screen: {error}");
 
                 false
             } else {
                 true
             },
+            graphics,
         }
     }
 
@@ -51,7 +56,7 @@ impl Terminal {
         }
 
         if self.alt_screen {
-            if let Err(error) = TTY::quit_alt_screen() {
+            if let Err(error) = Tty::quit_alt_screen(self.graphics) {
                 log::error!("Failed to quit alternative screen: {error}");
             }
 
@@ -60,57 +65,98 @@ impl Terminal {
     }
 }
 
-enum TTY {
+enum Tty {
     Raw(RawFd),
     File(File),
 }
 
 const SEQUENCES: [(u32, bool); 4] = [(1049, true), (1003, true), (1006, true), (25, false)];
 
-impl TTY {
-    fn stdin() -> TTY {
+/// Private mode leaving the cursor next to sixel images to prevent scrolling
+const SIXEL_SCROLLING_MODE: u32 = 8452;
+
+impl Tty {
+    fn stdin() -> Tty {
         let isatty = unsafe { libc::isatty(libc::STDIN_FILENO) };
 
         if isatty != 1 {
             if let Ok(file) = File::open("/dev/tty") {
-                return TTY::File(file);
+                return Tty::File(file);
             }
         }
 
-        TTY::Raw(libc::STDIN_FILENO)
+        Tty::Raw(libc::STDIN_FILENO)
     }
 
-    fn enter_alt_screen() -> io::Result<()> {
+    fn enter_alt_screen(graphics: Option<GraphicsProtocol>) -> io::Result<()> {
         let mut out = io::stdout();
 
         for (sequence, enable) in SEQUENCES {
             write!(out, "\x1b[?{}{}", sequence, if enable { "h" } else { "l" })?;
         }
 
+        if graphics == Some(GraphicsProtocol::Sixel) {
+            write!(out, "\x1b[?{SIXEL_SCROLLING_MODE}h")?;
+        }
+
+        // Push kitty keyboard protocol flags, ignored by other terminals
+        write!(out, "\x1b[>{KEYBOARD_FLAGS}u")?;
         // Set the current foreground color to black
         write!(out, "\x1b[48;2;0;0;0m")?;
         // Query current foreground color to for true-color support detection
         write!(out, "\x1bP$qm\x1b\\")?;
         // Query current terminal name
         write!(out, "\x1bP+q544e\x1b\\")?;
+        // Query support for synchronized output using DECRQM
+        write!(out, "\x1b[?{SYNCHRONIZED_OUTPUT_MODE}$p")?;
+        // Query the colors used by the terminal to match reduced color palettes
+        for index in 0..16 {
+            write!(out, "\x1b]4;{index};?\x1b\\")?;
+        }
+
+        write!(out, "\x1b]10;?\x1b\\\x1b]11;?\x1b\\")?;
+        // Query support for the kitty graphics protocol with a 1x1 image
+        if graphics == Some(GraphicsProtocol::Kitty) {
+            write!(
+                out,
+                "\x1b_Gi={GRAPHICS_QUERY_ID},s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\"
+            )?;
+        }
+
+        // Query the size of a cell in pixels, used to scale sixel images
+        // when the window size in pixels is unknown
+        if graphics == Some(GraphicsProtocol::Sixel) {
+            write!(out, "\x1b[16t")?;
+        }
+
+        // Request device attributes, reported after the replies to previous queries
+        write!(out, "\x1b[c")?;
 
         out.flush()
     }
 
-    fn quit_alt_screen() -> io::Result<()> {
+    fn quit_alt_screen(graphics: Option<GraphicsProtocol>) -> io::Result<()> {
         let mut out = io::stdout();
 
+        // Restore the keyboard protocol flags, which are stacked per screen,
+        // before leaving the alternate screen they were pushed on
+        write!(out, "\x1b[<u")?;
+
         for (sequence, enable) in SEQUENCES {
             write!(out, "\x1b[?{}{}", sequence, if enable { "l" } else { "h" })?;
         }
 
+        if graphics == Some(GraphicsProtocol::Sixel) {
+            write!(out, "\x1b[?{SIXEL_SCROLLING_MODE}l")?;
+        }
+
         out.flush()
     }
 
-    fn as_raw_fd(self) -> RawFd {
+    fn as_raw_fd(&self) -> RawFd {
         match self {
-            TTY::Raw(fd) => fd,
-            TTY::File(file) => file.as_raw_fd(),
+            Tty::Raw(fd) => *fd,
+            Tty::File(file) => file.as_raw_fd(),
         }
     }
 }
@@ -137,7 +183,7 @@ struct TerminalSettings {
 impl TerminalSettings {
     /// Fetch settings from the current TTY
     fn open() -> io::Result<Self> {
-        let tty = TTY::stdin();
+        let tty = Tty::stdin();
         let mut term = MaybeUninit::uninit();
         let data = unsafe {
             libc::tcgetattr(tty.as_raw_fd(), term.as_mut_ptr()).to_err()?;
@@ -171,7 +217,7 @@ impl TerminalSettings {
 
     /// Apply the settings to the current TTY
     fn apply(&self) -> io::Result<()> {
-        let tty = TTY::stdin();
+        let tty = Tty::stdin();
 
         unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSANOW, &self.data).to_err() }
     }
@@ -1,19 +1,47 @@
-// mod kd_tree;
-// mod quantizer;
+mod backend;
+mod block;
+mod braille;
+mod capture;
 mod cell;
+mod color_depth;
+mod dither;
 mod fidelitty;
 mod frame_sync;
+mod glyphs;
+mod graphics;
+mod iterm;
+mod kd_tree;
+mod kitty;
 mod painter;
 mod quad;
+mod quantizer;
+mod recording;
 mod render_thread;
 mod renderer;
+mod screenshot;
+mod sink;
+mod sixel;
+#[cfg(test)]
+mod vt;
 mod window;
 mod xterm;
 
+pub use backend::*;
+pub use block::*;
+pub use braille::*;
+pub use capture::*;
 pub use cell::*;
+pub use color_depth::*;
+pub use dither::*;
 pub use frame_sync::*;
+pub use glyphs::*;
+pub use graphics::*;
 pub use painter::*;
 pub use quad::*;
+pub use recording::*;
 pub use render_thread::*;
 pub use renderer::*;
+pub use screenshot::*;
+pub use sink::*;
 pub use window::*;
+pub use xterm::*;
@@ -0,0 +1,82 @@
+mod cpu;
+mod fidelitty;
+
+use std::str::FromStr;
+
+use crate::gfx::Size;
+
+use super::{Cell, Damage, GlyphSet};
+
+use self::{cpu::CpuBackend, fidelitty::FidelittyBackend};
+
+/// Converts page pixels to cells.
+pub trait RenderBackend {
+    fn kind(&self) -> Backend;
+
+    /// Set the size of the page in cells.
+    ///
+    /// Returns `false` if the backend cannot draw pages of this size.
+    fn set_size(&mut self, size: Size<usize>) -> bool;
+
+    /// Draw a damaged region of a BGRA8888 pixel array, 4x8 pixels per cell.
+    ///
+    /// `cells` contains the cells of the page, row by row.
+    fn draw(
+        &mut self,
+        pixels: &[u8],
+        pixels_size: Size<usize>,
+        damage: Damage,
+        cells: &mut [(Cell, Cell)],
+    );
+
+    /// Update cells with regions drawn asynchronously, called before painting
+    fn collect(&mut self, _cells: &mut [(Cell, Cell)]) {}
+
+    /// Whether drawn regions still need to be collected
+    fn has_pending(&self) -> bool {
+        false
+    }
+}
+
+/// Available render backends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    /// GPU rendering using fidelitty
+    Fidelitty,
+    /// CPU rendering using a set of glyphs
+    Cpu,
+}
+
+impl Backend {
+    const ALL: [Backend; 2] = [Backend::Fidelitty, Backend::Cpu];
+
+    /// Get the backend following this one
+    pub fn next(self) -> Backend {
+        let index = Self::ALL.iter().position(|&kind| kind == self).unwrap_or(0);
+
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Create an instance of the backend, or get the reason it is not available
+    pub fn create(self, glyphs: GlyphSet) -> Result<Box<dyn RenderBackend>, String> {
+        match self {
+            Backend::Fidelitty => match FidelittyBackend::new() {
+                Ok(backend) => Ok(Box::new(backend)),
+                Err(error) => Err(format!("fidelitty: {error}")),
+            },
+            Backend::Cpu => Ok(Box::new(CpuBackend::new(glyphs))),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fidelitty" => Ok(Self::Fidelitty),
+            "cpu" => Ok(Self::Cpu),
+            _ => Err(()),
+        }
+    }
+}
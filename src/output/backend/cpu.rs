@@ -0,0 +1,570 @@
+use std::{
+    ops::Range,
+    rc::Rc,
+    slice,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use crate::gfx::{Color, Size};
+
+use super::super::{
+    binarize_block, binarize_braille, binarize_octant, binarize_quandrant, binarize_sextant, Cell,
+    Damage, GlyphSet,
+};
+use super::{Backend, RenderBackend};
+
+/// Maximum number of threads drawing bands of rows
+const MAX_THREADS: usize = 8;
+/// Minimum number of damaged cells drawn by each thread
+const MIN_CELLS_PER_THREAD: usize = 2048;
+
+/// Approximates cells of pixels using a set of glyphs on the CPU.
+///
+/// Large damaged regions are split in bands of rows drawn on multiple threads.
+pub struct CpuBackend {
+    glyphs: GlyphSet,
+    size: Size<usize>,
+    /// Threads drawing bands other than the first one, shared between backends
+    workers: Rc<Workers>,
+    /// Cells of the damaged region computed by threads
+    fills: Vec<CellFill>,
+}
+
+/// Colors and character computed for a cell.
+#[derive(Clone, Copy)]
+struct CellFill {
+    quadrant: Option<(Color, Color, Color, Color)>,
+    background: Color,
+    foreground: Color,
+    codepoint: u32,
+}
+
+impl Default for CellFill {
+    fn default() -> Self {
+        Self {
+            quadrant: None,
+            background: Color::black(),
+            foreground: Color::black(),
+            codepoint: 0x20,
+        }
+    }
+}
+
+thread_local! {
+    /// Workers of the backends created on a thread, started with the first one
+    /// so switching backends or glyphs does not start new threads
+    static WORKERS: Rc<Workers> = {
+        let threads = thread::available_parallelism().map_or(1, |count| count.get());
+
+        Rc::new(Workers::new(threads.min(MAX_THREADS).saturating_sub(1)))
+    };
+}
+
+impl CpuBackend {
+    pub fn new(glyphs: GlyphSet) -> Self {
+        Self::with_workers(glyphs, WORKERS.with(Rc::clone))
+    }
+
+    #[cfg(test)]
+    fn with_threads(glyphs: GlyphSet, threads: usize) -> Self {
+        // The current thread draws the first band
+        Self::with_workers(glyphs, Rc::new(Workers::new(threads.saturating_sub(1))))
+    }
+
+    fn with_workers(glyphs: GlyphSet, workers: Rc<Workers>) -> Self {
+        Self {
+            glyphs,
+            size: Size::splat(0),
+            workers,
+            fills: Vec::new(),
+        }
+    }
+}
+
+impl RenderBackend for CpuBackend {
+    fn kind(&self) -> Backend {
+        Backend::Cpu
+    }
+
+    fn set_size(&mut self, size: Size<usize>) -> bool {
+        self.size = size;
+
+        true
+    }
+
+    fn draw(
+        &mut self,
+        pixels: &[u8],
+        pixels_size: Size<usize>,
+        damage: Damage,
+        cells: &mut [(Cell, Cell)],
+    ) {
+        let size = damage.size();
+        let row_length = pixels_size.width;
+
+        if damage.is_empty()
+            || row_length < damage.right * 4
+            || pixels.len() < row_length * damage.bottom * 8 * 4
+        {
+            return;
+        }
+
+        let threads = size.width * size.height / MIN_CELLS_PER_THREAD;
+        let threads = threads.clamp(1, self.workers.len() + 1);
+        let band_rows = size.height.div_ceil(threads);
+        let band = Band {
+            glyphs: self.glyphs,
+            pixels,
+            row_length,
+            columns: damage.left..damage.right,
+        };
+
+        self.fills.clear();
+        self.fills
+            .resize(size.width * size.height, CellFill::default());
+
+        self.workers
+            .draw(&band, damage.top, band_rows, &mut self.fills);
+
+        // Cells are not shared with threads as they contain graphemes
+        let rows = cells[damage.top * self.size.width..]
+            .chunks_exact_mut(self.size.width)
+            .zip(self.fills.chunks_exact(size.width));
+
+        for (row, fills) in rows {
+            for ((_, cell), fill) in row[damage.left..damage.right].iter_mut().zip(fills) {
+                if let Some(quadrant) = fill.quadrant {
+                    cell.quadrant = quadrant;
+                }
+
+                cell.background = fill.background;
+                cell.foreground = fill.foreground;
+                cell.codepoint = fill.codepoint;
+            }
+        }
+    }
+}
+
+/// Threads drawing bands of rows sent through channels, joined when dropped.
+struct Workers {
+    jobs: Vec<(Sender<Job>, JoinHandle<()>)>,
+    /// Completion of jobs, with whether the thread panicked
+    done: Receiver<bool>,
+}
+
+/// A band of rows drawn by a worker.
+struct Job {
+    band: Band<'static>,
+    top: usize,
+    fills: *mut CellFill,
+    length: usize,
+}
+
+// Jobs point to the pixels and cells of `Workers::draw`, which waits for them
+unsafe impl Send for Job {}
+
+impl Job {
+    fn run(self) {
+        let fills = unsafe { slice::from_raw_parts_mut(self.fills, self.length) };
+
+        self.band.draw(self.top, fills)
+    }
+}
+
+/// Signals the completion of a job, even if it panicked.
+struct Done<'a>(&'a Sender<bool>);
+
+impl Drop for Done<'_> {
+    fn drop(&mut self) {
+        self.0.send(thread::panicking()).ok();
+    }
+}
+
+/// Jobs sent to workers, waited for even when unwinding
+/// since they borrow the pixels and cells being drawn.
+struct Pending<'a> {
+    done: &'a Receiver<bool>,
+    count: usize,
+    panicked: bool,
+}
+
+impl Pending<'_> {
+    fn wait(&mut self) {
+        while self.count > 0 {
+            self.count -= 1;
+            self.panicked |= self.done.recv().unwrap_or(true);
+        }
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        self.wait()
+    }
+}
+
+impl Workers {
+    fn new(count: usize) -> Self {
+        let (done_tx, done) = mpsc::channel();
+        let jobs = (0..count)
+            .map(|_| {
+                let (tx, rx) = mpsc::channel::<Job>();
+                let done = done_tx.clone();
+                let handle = thread::spawn(move || {
+                    for job in rx {
+                        let _done = Done(&done);
+
+                        job.run()
+                    }
+                });
+
+                (tx, handle)
+            })
+            .collect();
+
+        Self { jobs, done }
+    }
+
+    fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Draw bands of `rows` rows of a region starting at row `top`,
+    /// the first band on the current thread and the others on workers.
+    fn draw(&self, band: &Band, top: usize, rows: usize, fills: &mut [CellFill]) {
+        let mut bands = fills.chunks_mut(rows * band.columns.len());
+        let first = bands.next().unwrap_or_default();
+        let mut pending = Pending {
+            done: &self.done,
+            count: 0,
+            panicked: false,
+        };
+
+        for (index, (fills, (jobs, _))) in bands.zip(&self.jobs).enumerate() {
+            let job = Job {
+                band: Band {
+                    pixels: unsafe {
+                        slice::from_raw_parts(band.pixels.as_ptr(), band.pixels.len())
+                    },
+                    columns: band.columns.clone(),
+                    ..*band
+                },
+                top: top + (index + 1) * rows,
+                fills: fills.as_mut_ptr(),
+                length: fills.len(),
+            };
+
+            match jobs.send(job) {
+                Ok(()) => pending.count += 1,
+                // Draw the band here if the worker stopped
+                Err(mpsc::SendError(job)) => job.run(),
+            }
+        }
+
+        band.draw(top, first);
+        pending.wait();
+
+        assert!(!pending.panicked, "a drawing thread panicked");
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        for (jobs, handle) in self.jobs.drain(..) {
+            drop(jobs);
+            handle.join().ok();
+        }
+    }
+}
+
+/// Draws bands of rows of a damaged region.
+struct Band<'a> {
+    glyphs: GlyphSet,
+    /// Page pixels encoded in BGRA8888, 4x8 pixels per cell
+    pixels: &'a [u8],
+    /// Width of the page in pixels
+    row_length: usize,
+    /// Damaged columns
+    columns: Range<usize>,
+}
+
+impl Band<'_> {
+    /// Draw rows of damaged cells, starting at row `top` of the page
+    fn draw(&self, top: usize, fills: &mut [CellFill]) {
+        let mut sums = vec![[0; 3]; self.columns.len() * 16];
+
+        for (index, row) in fills.chunks_exact_mut(self.columns.len()).enumerate() {
+            match self.glyphs {
+                GlyphSet::Quadrant => {
+                    for (fill, x) in row.iter_mut().zip(self.columns.clone()) {
+                        *fill = self.draw_quadrant(x, top + index)
+                    }
+
+                    continue;
+                }
+                GlyphSet::Block => {
+                    for (fill, x) in row.iter_mut().zip(self.columns.clone()) {
+                        *fill = self.draw_block(x, top + index)
+                    }
+
+                    continue;
+                }
+                _ => (),
+            }
+
+            self.sum_row(top + index, &mut sums);
+
+            for (fill, sums) in row.iter_mut().zip(sums.chunks_exact(16)) {
+                *fill = self.draw_cell(sums)
+            }
+        }
+    }
+
+    /// Sum the RGB channels of horizontal pairs of pixels in a row of cells.
+    ///
+    /// For each cell, `sums` contains 8 rows of 2 pairs.
+    fn sum_row(&self, y: usize, sums: &mut [[u16; 3]]) {
+        let length = self.columns.len() * 16;
+
+        for dy in 0..8 {
+            let start = ((y * 8 + dy) * self.row_length + self.columns.start * 4) * 4;
+            let pairs = self.pixels[start..start + length].chunks_exact(8);
+
+            // Written as plain integer arithmetic on fixed size chunks to be vectorized
+            for (index, pair) in pairs.enumerate() {
+                sums[index / 2 * 16 + dy * 2 + index % 2] = [
+                    pair[2] as u16 + pair[6] as u16,
+                    pair[1] as u16 + pair[5] as u16,
+                    pair[0] as u16 + pair[4] as u16,
+                ];
+            }
+        }
+    }
+
+    /// Sample a vertical pair of pixels at the top left of each quadrant of a cell
+    fn draw_quadrant(&self, x: usize, y: usize) -> CellFill {
+        let pixel = |dx: usize, dy: usize| {
+            let index = ((y * 8 + dy) * self.row_length + x * 4 + dx) * 4;
+
+            Color::new(
+                self.pixels[index + 2],
+                self.pixels[index + 1],
+                self.pixels[index],
+            )
+        };
+        let pair = |dx: usize, dy: usize| pixel(dx, dy).avg_with(pixel(dx, dy + 1));
+        let quadrant = (pair(0, 0), pair(2, 0), pair(2, 4), pair(0, 4));
+        let (ch, background, foreground) = binarize_quandrant(quadrant);
+
+        CellFill {
+            quadrant: Some(quadrant),
+            background,
+            foreground,
+            codepoint: ch.chars().next().unwrap_or(' ') as u32,
+        }
+    }
+
+    /// Match a glyph against every pixel of a cell
+    fn draw_block(&self, x: usize, y: usize) -> CellFill {
+        let mut block = [Color::black(); 32];
+
+        for dy in 0..8 {
+            let start = ((y * 8 + dy) * self.row_length + x * 4) * 4;
+            let pixels = self.pixels[start..start + 16].chunks_exact(4);
+
+            for (color, pixel) in block[dy * 4..dy * 4 + 4].iter_mut().zip(pixels) {
+                *color = Color::new(pixel[2], pixel[1], pixel[0]);
+            }
+        }
+
+        let (ch, background, foreground) = binarize_block(&block);
+
+        CellFill {
+            quadrant: None,
+            background,
+            foreground,
+            codepoint: ch as u32,
+        }
+    }
+
+    fn draw_cell(&self, sums: &[[u16; 3]]) -> CellFill {
+        // Average color of rows of pixels in the left or right half of the cell
+        let area = |rows: Range<usize>, half: usize| {
+            let count = rows.len() as u16 * 2;
+            let sum = rows.fold([0; 3], |sum: [u16; 3], dy| {
+                let pair = sums[dy * 2 + half];
+
+                [sum[0] + pair[0], sum[1] + pair[1], sum[2] + pair[2]]
+            });
+
+            Color::new(sum[0] / count, sum[1] / count, sum[2] / count).cast()
+        };
+        let fill = |(codepoint, background, foreground): (char, Color, Color)| CellFill {
+            quadrant: None,
+            background,
+            foreground,
+            codepoint: codepoint as u32,
+        };
+
+        match self.glyphs {
+            GlyphSet::Braille => {
+                // Each dot covers a 2x2 pixels block
+                let dot = |dx: usize, dy: usize| area(dy * 2..dy * 2 + 2, dx);
+
+                fill(binarize_braille([
+                    dot(0, 0),
+                    dot(1, 0),
+                    dot(0, 1),
+                    dot(1, 1),
+                    dot(0, 2),
+                    dot(1, 2),
+                    dot(0, 3),
+                    dot(1, 3),
+                ]))
+            }
+            GlyphSet::Sextant => {
+                // Rows of 8 pixels split in three parts of 3, 2 and 3 pixels
+                fill(binarize_sextant([
+                    area(0..3, 0),
+                    area(0..3, 1),
+                    area(3..5, 0),
+                    area(3..5, 1),
+                    area(5..8, 0),
+                    area(5..8, 1),
+                ]))
+            }
+            GlyphSet::Octant => {
+                // Each octant covers a 2x2 pixels block
+                let octant = |dx: usize, dy: usize| area(dy * 2..dy * 2 + 2, dx);
+
+                fill(binarize_octant([
+                    octant(0, 0),
+                    octant(1, 0),
+                    octant(0, 1),
+                    octant(1, 1),
+                    octant(0, 2),
+                    octant(1, 2),
+                    octant(0, 3),
+                    octant(1, 3),
+                ]))
+            }
+            // Drawn from the pixels by `draw_quadrant` and `draw_block`
+            GlyphSet::Quadrant | GlyphSet::Block => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLYPHS: [GlyphSet; 5] = [
+        GlyphSet::Quadrant,
+        GlyphSet::Braille,
+        GlyphSet::Sextant,
+        GlyphSet::Octant,
+        GlyphSet::Block,
+    ];
+
+    /// Generate a page of BGRA8888 pixels with gradients and noise
+    fn page(size: Size<usize>) -> Vec<u8> {
+        let mut seed = 0x2545f491u32;
+        let mut pixels = Vec::with_capacity(size.width * size.height * 32 * 4);
+
+        for y in 0..size.height * 8 {
+            for x in 0..size.width * 4 {
+                // xorshift32
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+
+                let noise = (seed & 0x3f) as u8;
+
+                pixels.extend_from_slice(&[
+                    (x as u8).wrapping_add(noise),
+                    (y as u8).wrapping_add(noise),
+                    (x ^ y) as u8,
+                    255,
+                ]);
+            }
+        }
+
+        pixels
+    }
+
+    fn draw(backend: &mut CpuBackend, size: Size<usize>, pixels: &[u8]) -> Vec<(Cell, Cell)> {
+        let mut cells = (0..size.width * size.height)
+            .map(|index| {
+                let (x, y) = ((index % size.width) as u32, (index / size.width) as u32);
+
+                (Cell::new(x, y), Cell::new(x, y))
+            })
+            .collect::<Vec<_>>();
+        let damage = Damage {
+            top: 0,
+            left: 0,
+            right: size.width,
+            bottom: size.height,
+        };
+
+        backend.set_size(size);
+        backend.draw(pixels, size * (4, 8), damage, &mut cells);
+
+        cells
+    }
+
+    #[test]
+    fn threads_draw_like_a_single_thread() {
+        let size = Size::new(160, 50);
+        let pixels = page(size);
+        let colors = |cells: Vec<(Cell, Cell)>| {
+            cells
+                .into_iter()
+                .map(|(_, cell)| (cell.background, cell.foreground, cell.codepoint))
+                .collect::<Vec<_>>()
+        };
+
+        for glyphs in GLYPHS {
+            let mut single = CpuBackend::with_threads(glyphs, 1);
+            let mut workers = CpuBackend::with_threads(glyphs, 4);
+
+            assert_eq!(
+                colors(draw(&mut single, size, &pixels)),
+                colors(draw(&mut workers, size, &pixels)),
+                "{glyphs:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn backends_share_workers() {
+        let first = CpuBackend::new(GlyphSet::Quadrant);
+        let second = CpuBackend::new(GlyphSet::Octant);
+
+        assert!(Rc::ptr_eq(&first.workers, &second.workers));
+    }
+
+    #[test]
+    fn workers_draw_consecutive_frames() {
+        let size = Size::new(160, 50);
+        let mut backend = CpuBackend::with_threads(GlyphSet::Quadrant, 4);
+        let first = page(size);
+        let mut second = first.clone();
+
+        second.reverse();
+        draw(&mut backend, size, &first);
+
+        let cells = draw(&mut backend, size, &second);
+        let expected = draw(
+            &mut CpuBackend::with_threads(GlyphSet::Quadrant, 1),
+            size,
+            &second,
+        );
+
+        assert!(cells
+            .iter()
+            .zip(&expected)
+            .all(|((_, cell), (_, expected))| cell == expected));
+    }
+}
@@ -0,0 +1,263 @@
+use std::{collections::VecDeque, time::Instant};
+
+use crate::{
+    gfx::{Color, Size},
+    output::fidelitty::{self, Context, FttyPixelFormat, Pipeline},
+    utils::log,
+};
+
+use super::super::{Cell, Damage};
+use super::{Backend, RenderBackend};
+
+/// Number of pipelines used in turns
+const PIPELINES: usize = 2;
+
+/// A region of the page dispatched to a pipeline.
+struct Dispatch {
+    pipeline: usize,
+    damage: Damage,
+    /// Whether the region was dispatched after the last collection
+    fresh: bool,
+    start: Instant,
+}
+
+/// Converts pixels to cells on the GPU using fidelitty.
+pub struct FidelittyBackend {
+    context: Context,
+    /// Pipelines used in turns, so a frame can be processed by the GPU
+    /// while the previous one is read back and painted
+    pipelines: Vec<Pipeline>,
+    /// Index of the pipeline used by the next dispatch
+    next: usize,
+    /// Dispatched regions waiting to be read back, oldest first
+    pending: VecDeque<Dispatch>,
+}
+
+impl FidelittyBackend {
+    pub fn new() -> fidelitty::Result<Self> {
+        let context = Context::new(PIPELINES as u8)?;
+
+        log::debug!("fidelitty: Vulkan context created");
+
+        Ok(Self {
+            context,
+            pipelines: Vec::with_capacity(PIPELINES),
+            next: 0,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Create or resize pipelines
+    fn resize(&mut self, size: Size<usize>) -> fidelitty::Result<()> {
+        if self.pipelines.is_empty() {
+            for _ in 0..PIPELINES {
+                let pipeline =
+                    self.context
+                        .create_pipeline(size, FttyPixelFormat::Bgra, Size::new(4, 8))?;
+
+                self.pipelines.push(pipeline);
+            }
+        } else {
+            for pipeline in self.pipelines.iter_mut() {
+                pipeline.resize(size)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the oldest dispatched region and read it back into cells
+    fn read(&mut self, cells: &mut [(Cell, Cell)]) {
+        let dispatch = match self.pending.pop_front() {
+            Some(dispatch) => dispatch,
+            None => return,
+        };
+        let pipeline = &mut self.pipelines[dispatch.pipeline];
+        let t_start = Instant::now();
+
+        if let Err(error) = pipeline.wait() {
+            log::debug!("fidelitty: {}", error);
+            return;
+        }
+
+        let t_gpu = t_start.elapsed();
+        let width = pipeline.size().width;
+
+        // Read fidelitty output into cells
+        let output = match pipeline.output() {
+            Ok(output) => output,
+            Err(error) => {
+                log::debug!("fidelitty: {}", error);
+                return;
+            }
+        };
+        let Damage {
+            top,
+            left,
+            right,
+            bottom,
+        } = dispatch.damage;
+
+        for y in top..bottom {
+            let row = y * width;
+
+            for cx in left..right {
+                let ftty_pixel = output[row + cx];
+                let cell = &mut cells[row + cx].1;
+
+                cell.background = Color::new(ftty_pixel.br, ftty_pixel.bg, ftty_pixel.bb);
+                cell.foreground = Color::new(ftty_pixel.fr, ftty_pixel.fg, ftty_pixel.fb);
+                cell.codepoint = ftty_pixel.codepoint;
+            }
+        }
+
+        let t_readback = t_start.elapsed();
+
+        log::debug!(
+            "fidelitty: {}x{} region read back from pipeline {} | wait: {:?} | readback: {:?} | since dispatch: {:?}",
+            right - left,
+            bottom - top,
+            dispatch.pipeline,
+            t_gpu,
+            t_readback - t_gpu,
+            dispatch.start.elapsed()
+        );
+    }
+}
+
+impl RenderBackend for FidelittyBackend {
+    fn kind(&self) -> Backend {
+        Backend::Fidelitty
+    }
+
+    fn set_size(&mut self, size: Size<usize>) -> bool {
+        // Regions dispatched at the previous size are no longer valid
+        self.pending.clear();
+
+        match self.resize(size) {
+            Ok(()) => true,
+            Err(error) => {
+                log::debug!("fidelitty: {}", error);
+
+                self.pipelines.clear();
+
+                false
+            }
+        }
+    }
+
+    fn draw(
+        &mut self,
+        pixels: &[u8],
+        pixels_size: Size<usize>,
+        damage: Damage,
+        cells: &mut [(Cell, Cell)],
+    ) {
+        if damage.is_empty() {
+            return;
+        }
+
+        let t_start = Instant::now();
+        let index = self.next;
+
+        // The next pipeline might still be processing an older frame
+        while self
+            .pending
+            .iter()
+            .any(|dispatch| dispatch.pipeline == index)
+        {
+            self.read(cells);
+        }
+
+        let t_wait = t_start.elapsed();
+
+        let Damage {
+            top,
+            left,
+            right,
+            bottom,
+        } = damage;
+        let pipeline = &mut self.pipelines[index];
+        let row_bytes = pipeline.input_row_bytes();
+        let src_row_bytes = pixels_size.width * 4;
+        let input = match pipeline.input() {
+            Ok(input) => input,
+            Err(error) => {
+                log::debug!("fidelitty: {}", error);
+                return;
+            }
+        };
+
+        // Copy BGRA pixels directly to fidelitty input surface.
+        // The GPU shader handles BGRA→RGB swizzle and 8→4 vertical downscale.
+        let rows = (top * 8)..(bottom * 8);
+
+        if src_row_bytes == row_bytes && pixels.len() >= rows.end * row_bytes {
+            // Strides match — single memcpy for the entire row block
+            let range = rows.start * row_bytes..rows.end * row_bytes;
+
+            input[range.clone()].copy_from_slice(&pixels[range]);
+        } else {
+            // Different strides — copy row by row
+            let (start, end) = (left * 4 * 4, right * 4 * 4);
+
+            if end > src_row_bytes {
+                return;
+            }
+
+            for py in rows {
+                let src = py * src_row_bytes;
+                let dst = py * row_bytes;
+
+                if src + end > pixels.len() {
+                    break;
+                }
+
+                input[dst + start..dst + end].copy_from_slice(&pixels[src + start..src + end]);
+            }
+        }
+
+        let t_copy = t_start.elapsed();
+
+        // Dispatch without waiting, the output is read back on the next collection
+        if let Err(error) = pipeline.execute(damage) {
+            log::debug!("fidelitty: {}", error);
+            return;
+        }
+
+        self.next = (index + 1) % PIPELINES;
+        self.pending.push_back(Dispatch {
+            pipeline: index,
+            damage,
+            fresh: true,
+            start: t_start,
+        });
+
+        log::debug!(
+            "fidelitty: {}x{} region dispatched to pipeline {} | wait: {:?} | copy: {:?}",
+            right - left,
+            bottom - top,
+            index,
+            t_wait,
+            t_copy - t_wait
+        );
+    }
+
+    /// Read back regions dispatched before the last collection.
+    ///
+    /// Regions dispatched since then keep being processed while we paint,
+    /// and are read back on the next collection.
+    fn collect(&mut self, cells: &mut [(Cell, Cell)]) {
+        while self.pending.front().is_some_and(|dispatch| !dispatch.fresh) {
+            self.read(cells);
+        }
+
+        for dispatch in self.pending.iter_mut() {
+            dispatch.fresh = false;
+        }
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
@@ -0,0 +1,164 @@
+use crate::gfx::Color;
+
+/// Glyphs matched against a block of pixels.
+///
+/// The complement of each shape is drawn by swapping colors,
+/// so glyphs covering the complement of another one are left out.
+/// Blocks are 4 pixels wide, so the left quarters are the only distinct
+/// left eighths, and the lower eighths cover every split of the 8 rows.
+#[rustfmt::skip]
+const CANDIDATES: [char; 24] = [
+    // Flat cell
+    ' ',
+    // Halves
+    '▀', '▌',
+    // Quadrants
+    '▘', '▝', '▖', '▗', '▚',
+    // Eighths
+    '▁', '▂', '▃', '▅', '▆', '▇', '▎', '▊',
+    // Wedges
+    '🬿', '🭀', '🭊', '🭋', '🭬', '🭭', '🭮', '🭯',
+];
+
+/// Mask of the pixels covered by each candidate, in row-major order on a 4x8 grid
+const MASKS: [u32; CANDIDATES.len()] = {
+    let mut masks = [0; CANDIDATES.len()];
+    let mut index = 0;
+
+    while index < CANDIDATES.len() {
+        let mut bit = 0;
+
+        while bit < 32 {
+            if covers(CANDIDATES[index], bit % 4, bit / 4) {
+                masks[index] |= 1 << bit;
+            }
+
+            bit += 1;
+        }
+
+        index += 1;
+    }
+
+    masks
+};
+
+/// Whether a glyph covers the center of a pixel in a 4x8 block
+const fn covers(glyph: char, x: u32, y: u32) -> bool {
+    // Pixel centers on a 16x16 grid spanning the cell
+    let (x, y) = (x * 4 + 2, y * 2 + 1);
+    let (left, top) = (x < 8, y < 8);
+
+    match glyph {
+        '▀' => top,
+        '▌' => left,
+        '▘' => top && left,
+        '▝' => top && !left,
+        '▖' => !top && left,
+        '▗' => !top && !left,
+        '▚' => top == left,
+        // Lower eighths
+        '▁'..='▇' => y > 16 - (glyph as u32 - 0x2580) * 2,
+        // Left eighths
+        '▉'..='▏' => x < (0x2590 - glyph as u32) * 2,
+        // Lower left and lower right wedges, cut by a diagonal between
+        // a corner of the cell and the edge of its sextant grid
+        '🬿' => 3 * y > 16 + 2 * x,
+        '🭀' => y > 2 * x,
+        '🭊' => 3 * y > 48 - 2 * x,
+        '🭋' => y > 32 - 2 * x,
+        // Triangular quarters
+        '🭬' => x < y && x < 16 - y,
+        '🭭' => y < x && y < 16 - x,
+        '🭮' => x > y && x > 16 - y,
+        '🭯' => y > x && y > 16 - x,
+        _ => false,
+    }
+}
+
+/// Turn a 4x8 grid of pixels in row-major order into a block character and two colors.
+///
+/// Every candidate glyph is scored by the squared error between the pixels
+/// and the average colors of the areas inside and outside of the glyph.
+pub fn binarize_block(pixels: &[Color; 32]) -> (char, Color, Color) {
+    let total = pixels
+        .iter()
+        .fold(Color::<u32>::splat(0), |sum, pixel| sum + pixel.cast());
+    // Minimizing the squared error is the same as maximizing the sum of
+    // the squared norms of the area sums, weighted by the area sizes
+    let score = |sum: Color<u32>, count: u32| match count {
+        0 => 0.0,
+        count => sum.cast::<f64>().dot(sum.cast()) / count as f64,
+    };
+    let mut best = (f64::MIN, 0, Color::<u32>::splat(0));
+
+    for (index, &mask) in MASKS.iter().enumerate() {
+        let mut inside = Color::<u32>::splat(0);
+        let mut bits = mask;
+
+        while bits != 0 {
+            inside = inside + pixels[bits.trailing_zeros() as usize].cast();
+            bits &= bits - 1;
+        }
+
+        let count = mask.count_ones();
+        let value = score(inside, count) + score(total - inside, 32 - count);
+
+        // Simpler glyphs come first and win ties
+        if value > best.0 {
+            best = (value, index, inside);
+        }
+    }
+
+    let (_, index, inside) = best;
+    let count = MASKS[index].count_ones();
+    let average = |sum: Color<u32>, count: u32| (sum / count.max(1)).cast();
+    let background = average(total - inside, 32 - count);
+
+    match count {
+        0 => (CANDIDATES[index], background, background),
+        count => (CANDIDATES[index], background, average(inside, count)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pixels drawn by a glyph and its colors
+    fn draw(glyph: char, background: Color, foreground: Color) -> [Color; 32] {
+        let index = CANDIDATES.iter().position(|&c| c == glyph).unwrap();
+
+        std::array::from_fn(|bit| match MASKS[index] & 1 << bit {
+            0 => background,
+            _ => foreground,
+        })
+    }
+
+    #[test]
+    fn matches_every_row_split() {
+        let (top, bottom) = (Color::new(200, 40, 10), Color::new(10, 90, 250));
+
+        for rows in 1..8 {
+            let pixels = std::array::from_fn(|bit| if bit / 4 < rows { top } else { bottom });
+            let (glyph, background, foreground) = binarize_block(&pixels);
+
+            assert_eq!(draw(glyph, background, foreground), pixels, "{rows} rows");
+        }
+    }
+
+    #[test]
+    fn matches_every_column_split() {
+        let (left, right) = (Color::new(200, 40, 10), Color::new(10, 90, 250));
+
+        for columns in 1..4 {
+            let pixels = std::array::from_fn(|bit| if bit % 4 < columns { left } else { right });
+            let (glyph, background, foreground) = binarize_block(&pixels);
+
+            assert_eq!(
+                draw(glyph, background, foreground),
+                pixels,
+                "{columns} columns"
+            );
+        }
+    }
+}
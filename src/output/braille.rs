@@ -0,0 +1,74 @@
+use crate::gfx::Color;
+
+use super::split_colors;
+
+/// Bit of each dot in a Braille pattern, in row-major order on a 2x4 grid
+const DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+/// Turn a 2x4 grid of colors in row-major order into a Braille character and two colors.
+///
+/// Dots are used for the smallest group of colors, to keep them readable.
+pub fn binarize_braille(dots: [Color; 8]) -> (char, Color, Color) {
+    let (mask, dark, bright) = split_colors(&dots);
+    let (mask, background, foreground) = if mask.count_ones() <= 4 {
+        (mask, dark, bright)
+    } else {
+        (!mask & 0xff, bright, dark)
+    };
+    let pattern = DOTS
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| mask & 1 << index != 0)
+        .fold(0, |pattern, (_, dot)| pattern | dot);
+
+    (
+        char::from_u32(0x2800 + pattern).unwrap_or(' '),
+        background,
+        foreground,
+    )
+}
+
+/// Get the dots of a Braille character, as a mask in row-major order on a 2x4 grid
+pub fn braille_mask(ch: char) -> Option<u32> {
+    let pattern = (ch as u32)
+        .checked_sub(0x2800)
+        .filter(|&pattern| pattern < 0x100)?;
+
+    Some(
+        DOTS.iter()
+            .enumerate()
+            .filter(|(_, &dot)| pattern & dot != 0)
+            .fold(0, |mask, (index, _)| mask | 1 << index),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_dots_in_columns() {
+        let (dark, bright) = (Color::black(), Color::new(255, 255, 255));
+        // Position of dots 1 to 8 on the grid
+        let positions = [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 0),
+            (1, 1),
+            (1, 2),
+            (0, 3),
+            (1, 3),
+        ];
+
+        for (dot, (x, y)) in positions.into_iter().enumerate() {
+            let index = y * 2 + x;
+            let colors = std::array::from_fn(|i| if i == index { bright } else { dark });
+            let (ch, background, foreground) = binarize_braille(colors);
+
+            assert_eq!(ch as u32, 0x2800 + (1 << dot), "dot {}", dot + 1);
+            assert_eq!((background, foreground), (dark, bright));
+            assert_eq!(braille_mask(ch), Some(1 << index));
+        }
+    }
+}
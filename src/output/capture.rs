@@ -0,0 +1,148 @@
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    gfx::Size,
+    utils::{log, png},
+};
+
+/// Path of captures without `--capture`, `{}` being replaced by the frame number
+const DEFAULT_PATH: &str = "carbonyl-{}.png";
+
+/// Bitmaps to capture, numbered from 1 in the order they are rendered.
+///
+/// Bitmaps received by the rendering thread before its next frame are merged,
+/// so a number counts merged bitmaps rather than every bitmap drawn by the browser.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptureFrames {
+    /// A single bitmap
+    Index(u64),
+    /// Every `n`th bitmap
+    Every(u64),
+}
+
+impl CaptureFrames {
+    fn contains(&self, frame: u64) -> bool {
+        match *self {
+            CaptureFrames::Index(index) => frame == index,
+            CaptureFrames::Every(count) => frame.is_multiple_of(count),
+        }
+    }
+}
+
+impl FromStr for CaptureFrames {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (every, count) = match value.strip_prefix("every:") {
+            Some(count) => (true, count),
+            None => (false, value),
+        };
+
+        match count.parse().map_err(|_| ())? {
+            0 => Err(()),
+            count if every => Ok(Self::Every(count)),
+            index => Ok(Self::Index(index)),
+        }
+    }
+}
+
+/// Saves the BGRA8888 bitmaps rendered from the browser,
+/// before they are converted to cells.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    /// Path of the images, `{}` being replaced by the frame number
+    path: PathBuf,
+    frames: Option<CaptureFrames>,
+    /// Number of bitmaps rendered
+    frame: u64,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl Capture {
+    /// Create a capture saving to `path`, which gets a `-{}` suffix before its
+    /// extension if it lacks one while capturing every `n`th bitmap.
+    pub fn new(path: Option<PathBuf>, frames: Option<CaptureFrames>) -> Capture {
+        let mut path = path.unwrap_or_else(|| DEFAULT_PATH.into());
+
+        if matches!(frames, Some(CaptureFrames::Every(_))) && !path.to_string_lossy().contains("{}")
+        {
+            let mut name = path.file_stem().unwrap_or_default().to_os_string();
+
+            name.push("-{}");
+
+            if let Some(extension) = path.extension() {
+                name.push(".");
+                name.push(extension);
+            }
+
+            path.set_file_name(name);
+        }
+
+        Capture {
+            path,
+            frames,
+            frame: 0,
+        }
+    }
+
+    /// Count a bitmap rendered from the browser, and save it if selected
+    pub fn receive(&mut self, pixels: &[u8], size: Size<usize>) {
+        self.frame += 1;
+
+        if self
+            .frames
+            .is_some_and(|frames| frames.contains(self.frame))
+        {
+            self.save(pixels, size);
+        }
+    }
+
+    /// Save a bitmap as the current frame, logging the result
+    pub fn save(&self, pixels: &[u8], size: Size<usize>) {
+        let path = self.path.to_string_lossy();
+        let path = path.replace("{}", &self.frame.to_string());
+
+        match save_bitmap(Path::new(&path), pixels, size) {
+            Ok(()) => log::debug!("capture: saved frame {} to {}", self.frame, path),
+            Err(error) => log::warning!("capture: failed to save {}: {}", path, error),
+        }
+    }
+}
+
+/// Save BGRA8888 pixels as a PNG image if the path ends with `.png`, or as a PPM image otherwise.
+pub fn save_bitmap(path: &Path, pixels: &[u8], size: Size<usize>) -> io::Result<()> {
+    let rgb = pixels
+        .chunks_exact(4)
+        .take(size.width * size.height)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0]])
+        .collect::<Vec<_>>();
+
+    if rgb.len() < size.width * size.height * 3 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "bitmap smaller than its size",
+        ));
+    }
+
+    let mut out = Vec::new();
+
+    match path.extension().and_then(OsStr::to_str) {
+        Some("png") => png::encode(&rgb, size.width, size.height, &mut out),
+        _ => {
+            out.extend_from_slice(format!("P6\n{} {}\n255\n", size.width, size.height).as_bytes());
+            out.extend_from_slice(&rgb);
+        }
+    }
+
+    fs::write(path, out)
+}
@@ -21,6 +21,8 @@ pub struct Cell {
     pub background: Color,
     pub foreground: Color,
     pub codepoint: u32,
+    /// Dithered xterm palette indices of the background and foreground
+    pub palette: Option<(u8, u8)>,
 }
 
 impl Cell {
@@ -32,6 +34,7 @@ impl Cell {
             background: Color::black(),
             foreground: Color::black(),
             codepoint: 0x20, // space
+            palette: None,
         }
     }
 }
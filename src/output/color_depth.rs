@@ -0,0 +1,70 @@
+use std::{env, str::FromStr};
+
+use crate::utils::terminfo::{self, Terminfo};
+
+/// Number of colors the terminal is able to display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorDepth {
+    /// 24-bit colors
+    TrueColor,
+    /// xterm 256 colors palette
+    Xterm256,
+    /// 16 ANSI colors
+    Ansi16,
+    /// Default foreground and background only
+    Mono,
+}
+
+impl ColorDepth {
+    /// Detect the color depth using `COLORTERM`, `TERM` and terminfo
+    pub fn detect() -> ColorDepth {
+        if let "truecolor" | "24bit" = env::var("COLORTERM").unwrap_or_default().as_str() {
+            return ColorDepth::TrueColor;
+        }
+
+        let term = env::var("TERM").unwrap_or_default();
+
+        match term.as_str() {
+            "" => return ColorDepth::Xterm256,
+            "dumb" => return ColorDepth::Mono,
+            term if term.ends_with("-direct") => return ColorDepth::TrueColor,
+            term if term.contains("256color") => return ColorDepth::Xterm256,
+            _ => (),
+        }
+
+        let colors = Terminfo::load(&term).map(|info| info.number(terminfo::COLORS));
+
+        match colors {
+            Some(colors) if colors >= 1 << 24 => ColorDepth::TrueColor,
+            // Terminals like xterm report 8 colors but support 256,
+            // only trust smaller counts from terminals known to be limited
+            Some(colors) if is_limited(&term) && colors < 8 => ColorDepth::Mono,
+            // Consoles such as Linux's support 16 colors
+            _ if is_limited(&term) => ColorDepth::Ansi16,
+            // Assume a modern terminal emulator otherwise
+            _ => ColorDepth::Xterm256,
+        }
+    }
+}
+
+/// Whether a terminal is a console or a hardware terminal unable to display 256 colors
+fn is_limited(term: &str) -> bool {
+    let name = term.split('-').next().unwrap_or_default();
+
+    matches!(name, "linux" | "ansi" | "cons25" | "sun" | "wsvt25")
+        || name.starts_with("vt")
+}
+
+impl FromStr for ColorDepth {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "truecolor" | "24bit" => Ok(Self::TrueColor),
+            "256" => Ok(Self::Xterm256),
+            "16" => Ok(Self::Ansi16),
+            "mono" => Ok(Self::Mono),
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::str::FromStr;
+
+use crate::gfx::Color;
+
+use super::XtermPalette;
+
+/// Dithering applied to cell colors when mapping them to the xterm palette.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dither {
+    /// Map every color to its nearest palette entry
+    None,
+    /// 4x4 Bayer matrix threshold
+    Ordered,
+    /// Floyd-Steinberg error diffusion
+    Diffusion,
+}
+
+impl FromStr for Dither {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(Self::None),
+            "ordered" => Ok(Self::Ordered),
+            "diffusion" => Ok(Self::Diffusion),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Thresholds of a 4x4 Bayer matrix, out of 16
+const BAYER: [[i32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+/// Amplitude of the ordered dithering noise, about the distance between two palette levels
+const SPREAD: i32 = 32;
+
+impl Dither {
+    /// Map a grid of colors stored row by row to xterm palette indices
+    pub fn apply(&self, colors: &[Color], width: usize, palette: &mut XtermPalette) -> Vec<u8> {
+        match self {
+            Dither::None => colors
+                .iter()
+                .map(|color| palette.index_of(*color))
+                .collect(),
+            Dither::Ordered => colors
+                .iter()
+                .enumerate()
+                .map(|(index, color)| {
+                    let threshold = BAYER[index / width.max(1) % 4][index % width.max(1) % 4];
+                    let offset = (threshold * 2 + 1) * SPREAD / 32 - SPREAD / 2;
+
+                    palette.index_of(clamp(color.cast::<i32>() + offset))
+                })
+                .collect(),
+            Dither::Diffusion => diffuse(colors, width, palette),
+        }
+    }
+}
+
+/// Floyd-Steinberg dithering, spreading the error of every cell to its neighbors
+fn diffuse(colors: &[Color], width: usize, palette: &mut XtermPalette) -> Vec<u8> {
+    let mut codes = Vec::with_capacity(colors.len());
+    // Error accumulated for the current and the next row, with a cell of padding on each side
+    let mut current = vec![Color::<i32>::splat(0); width + 2];
+    let mut next = current.clone();
+
+    for row in colors.chunks(width.max(1)) {
+        for (x, color) in row.iter().enumerate() {
+            let wanted = clamp(color.cast::<i32>() + current[x + 1] / 16);
+            let code = palette.index_of(wanted);
+            let error = wanted.cast::<i32>() - palette.color(code).cast::<i32>();
+
+            current[x + 2] = current[x + 2] + error * 7;
+            next[x] = next[x] + error * 3;
+            next[x + 1] = next[x + 1] + error * 5;
+            next[x + 2] = next[x + 2] + error;
+            codes.push(code);
+        }
+
+        std::mem::swap(&mut current, &mut next);
+        next.fill(Color::splat(0));
+    }
+
+    codes
+}
+
+fn clamp(color: Color<i32>) -> Color {
+    color.map(|channel| channel.clamp(0, 255) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 16;
+
+    /// Dither a flat region, returning the indices and the average of their colors
+    fn flat(dither: Dither, color: Color) -> (Vec<u8>, Color<u32>) {
+        let mut palette = XtermPalette::new();
+        let codes = dither.apply(&[color; WIDTH * WIDTH], WIDTH, &mut palette);
+        let sum = codes.iter().fold(Color::splat(0), |sum, &code| {
+            sum + palette.color(code).cast::<u32>()
+        });
+
+        (codes, sum / (WIDTH * WIDTH) as u32)
+    }
+
+    #[test]
+    fn keeps_palette_colors() {
+        let red = Color::from_xterm(196);
+
+        for dither in [Dither::None, Dither::Ordered, Dither::Diffusion] {
+            let (codes, _) = flat(dither, red);
+
+            assert!(codes.iter().all(|&code| code == 196), "{dither:?}");
+        }
+    }
+
+    #[test]
+    fn orders_thresholds_in_tiles() {
+        // Between the 6x6x6 cube levels 95 and 135
+        let (codes, average) = flat(Dither::Ordered, Color::splat(115));
+
+        assert!(codes.iter().any(|&code| code != codes[0]));
+        // Every 4x4 tile repeats the same pattern
+        for (index, &code) in codes.iter().enumerate() {
+            let (x, y) = (index % WIDTH, index / WIDTH);
+
+            assert_eq!(code, codes[y % 4 * WIDTH + x % 4], "{x},{y}");
+        }
+
+        assert!(average.r.abs_diff(115) <= 10, "{average:?}");
+    }
+
+    #[test]
+    fn diffuses_errors() {
+        let color = Color::new(100, 150, 30);
+        let (_, nearest) = flat(Dither::None, color);
+        let (codes, average) = flat(Dither::Diffusion, color);
+        let error = |average: Color<u32>| {
+            let diff = average.cast::<i32>() - color.cast::<i32>();
+
+            diff.dot(diff)
+        };
+
+        assert!(codes.iter().any(|&code| code != codes[0]));
+        assert!(error(average) < error(nearest), "{average:?}");
+    }
+}
@@ -1,57 +1,294 @@
+mod ffi;
+
+use std::{fmt, ptr::NonNull, rc::Rc, slice};
+
 use libc::c_void;
 
-#[repr(C)]
-#[derive(Copy, Clone)]
-pub struct FttyUnicodePixel {
-    pub br: u8,
-    pub bg: u8,
-    pub bb: u8,
-    pub fr: u8,
-    pub fg: u8,
-    pub fb: u8,
-    pub _pad: u16,
-    pub codepoint: u32,
-}
-
-pub type FttyContext = *mut c_void;
-pub type FttyPipeline = *mut c_void;
-
-#[repr(C)]
-pub enum FttyPixelFormat {
-    Rgb = 0,
-    Bgra = 1,
-}
-
-extern "C" {
-    pub fn ftty_context_create(max_pipelines: u8) -> FttyContext;
-    pub fn ftty_context_destroy(ctx: FttyContext);
-
-    pub fn ftty_context_create_render_pipeline_ex(
-        ctx: FttyContext,
-        w: u16,
-        h: u16,
-        pixel_format: FttyPixelFormat,
-        src_cell_w: u8,
-        src_cell_h: u8,
-    ) -> FttyPipeline;
-    pub fn ftty_context_destroy_render_pipeline(ctx: FttyContext, handle: FttyPipeline);
-    pub fn ftty_context_resize_render_pipeline(
-        ctx: FttyContext,
-        handle: FttyPipeline,
-        w: u16,
-        h: u16,
-    ) -> i32;
-
-    pub fn ftty_context_execute_render_pipeline_region(
-        ctx: FttyContext,
-        handle: FttyPipeline,
-        dispatch_x: u16,
-        dispatch_y: u16,
-        dispatch_w: u16,
-        dispatch_h: u16,
-    ) -> i32;
-    pub fn ftty_context_wait_render_pipeline(ctx: FttyContext, handle: FttyPipeline) -> i32;
-
-    pub fn ftty_pipeline_get_input_surface(handle: FttyPipeline) -> *mut u8;
-    pub fn ftty_pipeline_get_output_surface(handle: FttyPipeline) -> *mut FttyUnicodePixel;
+use crate::gfx::Size;
+
+use super::Damage;
+
+use ffi::Library;
+
+pub use ffi::{FttyPixelFormat, FttyUnicodePixel};
+
+/// An error returned by fidelitty.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// libfidelitty could not be loaded
+    Library(&'static str),
+    /// No Vulkan device could be used
+    Unavailable,
+    /// The pipeline size does not fit in 16 bits
+    InvalidSize(Size<usize>),
+    /// The dispatched region is out of the pipeline bounds
+    InvalidRegion(Damage),
+    CreatePipeline,
+    Resize(i32),
+    Execute(i32),
+    Wait(i32),
+    /// A pipeline surface is not mapped
+    Surface,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Library(reason) => write!(f, "failed to load library: {reason}"),
+            Error::Unavailable => write!(f, "Vulkan not available"),
+            Error::InvalidSize(size) => {
+                write!(f, "invalid pipeline size {}x{}", size.width, size.height)
+            }
+            Error::InvalidRegion(region) => write!(
+                f,
+                "invalid region {}x{} at {},{}",
+                region.right.saturating_sub(region.left),
+                region.bottom.saturating_sub(region.top),
+                region.left,
+                region.top
+            ),
+            Error::CreatePipeline => write!(f, "failed to create render pipeline"),
+            Error::Resize(code) => write!(f, "failed to resize render pipeline ({code})"),
+            Error::Execute(code) => write!(f, "failed to execute render pipeline ({code})"),
+            Error::Wait(code) => write!(f, "failed to wait for render pipeline ({code})"),
+            Error::Surface => write!(f, "pipeline surface not available"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Owned fidelitty context, destroyed once every pipeline is dropped.
+struct RawContext {
+    library: &'static Library,
+    raw: NonNull<c_void>,
+}
+
+impl RawContext {
+    fn as_ptr(&self) -> ffi::FttyContext {
+        self.raw.as_ptr()
+    }
+}
+
+impl Drop for RawContext {
+    fn drop(&mut self) {
+        unsafe { (self.library.ftty_context_destroy)(self.as_ptr()) }
+    }
+}
+
+/// A fidelitty Vulkan context.
+pub struct Context {
+    raw: Rc<RawContext>,
+}
+
+impl Context {
+    /// Load libfidelitty if needed and create a context
+    pub fn new(max_pipelines: u8) -> Result<Context> {
+        let library = ffi::library().map_err(Error::Library)?;
+        let raw = unsafe { (library.ftty_context_create)(max_pipelines) };
+
+        match NonNull::new(raw) {
+            Some(raw) => Ok(Context {
+                raw: Rc::new(RawContext { library, raw }),
+            }),
+            None => Err(Error::Unavailable),
+        }
+    }
+
+    /// Create a pipeline converting pixels to `size` cells,
+    /// using `cell_size` source pixels per cell
+    pub fn create_pipeline(
+        &self,
+        size: Size<usize>,
+        format: FttyPixelFormat,
+        cell_size: Size<u8>,
+    ) -> Result<Pipeline> {
+        let (w, h) = dimensions(size)?;
+        let raw = unsafe {
+            (self.raw.library.ftty_context_create_render_pipeline_ex)(
+                self.raw.as_ptr(),
+                w,
+                h,
+                format,
+                cell_size.width,
+                cell_size.height,
+            )
+        };
+
+        match NonNull::new(raw) {
+            Some(raw) => Ok(Pipeline {
+                context: self.raw.clone(),
+                raw,
+                size,
+                format,
+                cell_size: cell_size.cast(),
+                busy: false,
+            }),
+            None => Err(Error::CreatePipeline),
+        }
+    }
+}
+
+/// A render pipeline, converting an input surface of pixels
+/// to an output surface of unicode cells.
+///
+/// Surfaces can only be accessed while the GPU is not using them,
+/// the pipeline waits for any pending execution when needed.
+pub struct Pipeline {
+    context: Rc<RawContext>,
+    raw: NonNull<c_void>,
+    /// Size of the output in cells
+    size: Size<usize>,
+    format: FttyPixelFormat,
+    /// Size of a cell in input pixels
+    cell_size: Size<usize>,
+    /// Whether an execution has been dispatched and not waited for
+    busy: bool,
+}
+
+impl Pipeline {
+    pub fn size(&self) -> Size<usize> {
+        self.size
+    }
+
+    /// Number of bytes in a row of the input surface
+    pub fn input_row_bytes(&self) -> usize {
+        let bpp = match self.format {
+            FttyPixelFormat::Rgb => 3,
+            FttyPixelFormat::Bgra => 4,
+        };
+
+        self.size.width * self.cell_size.width * bpp
+    }
+
+    pub fn resize(&mut self, size: Size<usize>) -> Result<()> {
+        let (w, h) = dimensions(size)?;
+
+        self.wait()?;
+
+        let ret = unsafe {
+            (self.context.library.ftty_context_resize_render_pipeline)(
+                self.context.as_ptr(),
+                self.raw.as_ptr(),
+                w,
+                h,
+            )
+        };
+
+        if ret != 0 {
+            return Err(Error::Resize(ret));
+        }
+
+        self.size = size;
+
+        Ok(())
+    }
+
+    /// Get the input surface, with a row of pixels every `input_row_bytes()`
+    pub fn input(&mut self) -> Result<&mut [u8]> {
+        self.wait()?;
+
+        let len = self.input_row_bytes() * self.size.height * self.cell_size.height;
+        let surface =
+            unsafe { (self.context.library.ftty_pipeline_get_input_surface)(self.raw.as_ptr()) };
+
+        if surface.is_null() {
+            return Err(Error::Surface);
+        }
+
+        Ok(unsafe { slice::from_raw_parts_mut(surface, len) })
+    }
+
+    /// Get the output surface, with a pixel per cell
+    pub fn output(&mut self) -> Result<&[FttyUnicodePixel]> {
+        self.wait()?;
+
+        let len = self.size.width * self.size.height;
+        let surface =
+            unsafe { (self.context.library.ftty_pipeline_get_output_surface)(self.raw.as_ptr()) };
+
+        if surface.is_null() {
+            return Err(Error::Surface);
+        }
+
+        Ok(unsafe { slice::from_raw_parts(surface, len) })
+    }
+
+    /// Dispatch the conversion of a region of cells without waiting for it
+    pub fn execute(&mut self, region: Damage) -> Result<()> {
+        if region.is_empty() || region.right > self.size.width || region.bottom > self.size.height {
+            return Err(Error::InvalidRegion(region));
+        }
+
+        self.wait()?;
+
+        let ret = unsafe {
+            (self
+                .context
+                .library
+                .ftty_context_execute_render_pipeline_region)(
+                self.context.as_ptr(),
+                self.raw.as_ptr(),
+                region.left as u16,
+                region.top as u16,
+                (region.right - region.left) as u16,
+                (region.bottom - region.top) as u16,
+            )
+        };
+
+        if ret != 0 {
+            return Err(Error::Execute(ret));
+        }
+
+        self.busy = true;
+
+        Ok(())
+    }
+
+    /// Wait for the dispatched region to be converted
+    pub fn wait(&mut self) -> Result<()> {
+        if !self.busy {
+            return Ok(());
+        }
+
+        let ret = unsafe {
+            (self.context.library.ftty_context_wait_render_pipeline)(
+                self.context.as_ptr(),
+                self.raw.as_ptr(),
+            )
+        };
+
+        match ret {
+            0 => {
+                self.busy = false;
+
+                Ok(())
+            }
+            // The GPU might still be using the surfaces
+            code => Err(Error::Wait(code)),
+        }
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        // The GPU must be done with the pipeline before destroying it
+        self.wait().ok();
+
+        unsafe {
+            (self.context.library.ftty_context_destroy_render_pipeline)(
+                self.context.as_ptr(),
+                self.raw.as_ptr(),
+            )
+        }
+    }
+}
+
+fn dimensions(size: Size<usize>) -> Result<(u16, u16)> {
+    match (u16::try_from(size.width), u16::try_from(size.height)) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(Error::InvalidSize(size)),
+    }
 }
@@ -0,0 +1,146 @@
+use std::{
+    ffi::{CStr, CString},
+    mem,
+    sync::OnceLock,
+};
+
+use libc::c_void;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FttyUnicodePixel {
+    pub br: u8,
+    pub bg: u8,
+    pub bb: u8,
+    pub fr: u8,
+    pub fg: u8,
+    pub fb: u8,
+    pub _pad: u16,
+    pub codepoint: u32,
+}
+
+pub type FttyContext = *mut c_void;
+pub type FttyPipeline = *mut c_void;
+
+#[repr(C)]
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FttyPixelFormat {
+    Rgb = 0,
+    Bgra = 1,
+}
+
+#[cfg(target_os = "macos")]
+const LIBRARY_NAMES: &[&str] = &["libfidelitty.dylib"];
+#[cfg(not(target_os = "macos"))]
+const LIBRARY_NAMES: &[&str] = &["libfidelitty.so.0", "libfidelitty.so"];
+
+macro_rules! library {
+    ($($name:ident: fn($($arg:ident: $type:ty),*) $(-> $ret:ty)?;)*) => {
+        /// Functions exported by libfidelitty, resolved at runtime.
+        pub struct Library {
+            $(pub $name: unsafe extern "C" fn($($arg: $type),*) $(-> $ret)?,)*
+        }
+
+        impl Library {
+            fn open() -> Result<Library, String> {
+                let handle = open_library(LIBRARY_NAMES)?;
+
+                Ok(Library {
+                    $($name: unsafe { symbol(handle, stringify!($name))? },)*
+                })
+            }
+        }
+    };
+}
+
+library! {
+    ftty_context_create: fn(max_pipelines: u8) -> FttyContext;
+    ftty_context_destroy: fn(ctx: FttyContext);
+
+    ftty_context_create_render_pipeline_ex: fn(
+        ctx: FttyContext,
+        w: u16,
+        h: u16,
+        pixel_format: FttyPixelFormat,
+        src_cell_w: u8,
+        src_cell_h: u8
+    ) -> FttyPipeline;
+    ftty_context_destroy_render_pipeline: fn(ctx: FttyContext, handle: FttyPipeline);
+    ftty_context_resize_render_pipeline: fn(
+        ctx: FttyContext,
+        handle: FttyPipeline,
+        w: u16,
+        h: u16
+    ) -> i32;
+
+    ftty_context_execute_render_pipeline_region: fn(
+        ctx: FttyContext,
+        handle: FttyPipeline,
+        dispatch_x: u16,
+        dispatch_y: u16,
+        dispatch_w: u16,
+        dispatch_h: u16
+    ) -> i32;
+    ftty_context_wait_render_pipeline: fn(ctx: FttyContext, handle: FttyPipeline) -> i32;
+
+    ftty_pipeline_get_input_surface: fn(handle: FttyPipeline) -> *mut u8;
+    ftty_pipeline_get_output_surface: fn(handle: FttyPipeline) -> *mut FttyUnicodePixel;
+}
+
+static LIBRARY: OnceLock<Result<Library, String>> = OnceLock::new();
+
+/// Load libfidelitty on first use.
+///
+/// Returns the reason the library could not be loaded, if any.
+pub fn library() -> Result<&'static Library, &'static str> {
+    match LIBRARY.get_or_init(Library::open) {
+        Ok(library) => Ok(library),
+        Err(reason) => Err(reason),
+    }
+}
+
+/// Open the first library found from a list of names.
+///
+/// The library is never closed, its symbols are used until the process exits.
+fn open_library(names: &[&str]) -> Result<*mut c_void, String> {
+    let mut errors = Vec::new();
+
+    for name in names {
+        let name = CString::new(*name).unwrap();
+        let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+
+        if !handle.is_null() {
+            return Ok(handle);
+        }
+
+        errors.push(dl_error());
+    }
+
+    Err(errors.join("; "))
+}
+
+/// Resolve a function exported by the library
+unsafe fn symbol<T: Copy>(handle: *mut c_void, name: &str) -> Result<T, String> {
+    let symbol = CString::new(name).unwrap();
+    let address = libc::dlsym(handle, symbol.as_ptr());
+
+    if address.is_null() {
+        return Err(dl_error());
+    }
+
+    Ok(mem::transmute_copy(&address))
+}
+
+/// Get the last error reported by the dynamic linker
+fn dl_error() -> String {
+    let error = unsafe { libc::dlerror() };
+
+    if error.is_null() {
+        return "unknown error".to_owned();
+    }
+
+    unsafe { CStr::from_ptr(error) }
+        .to_string_lossy()
+        .into_owned()
+}
@@ -1,16 +1,49 @@
 use std::time::{Duration, Instant};
 
+/// Weight of the last frame in moving averages
+const SMOOTHING: f32 = 0.2;
+/// Fraction of the frame duration we allow to be spent writing to the terminal
+const WRITE_BUDGET: f32 = 0.5;
+/// Minimum amount of pending output bytes considered as a backlog
+const BACKLOG_BYTES: f32 = 4096.0;
+
+/// Measurements of a rendered frame
+#[derive(Clone, Copy, Debug)]
+pub struct FrameStats {
+    /// Number of bytes written to the terminal
+    pub bytes: usize,
+    /// Time spent writing and flushing the frame
+    pub flush: Duration,
+}
+
 /// A utility to synchronize rendering with a given FPS
 pub struct FrameSync {
     render_start: Option<Instant>,
     frame_duration: Duration,
+    /// Frame durations at the maximum and minimum FPS
+    min_duration: Duration,
+    max_duration: Duration,
+    /// Moving average of the time spent writing a frame, in seconds
+    flush: f32,
+    /// Moving average of the size of a frame, in bytes
+    bytes: f32,
+    /// Moving average of the rate at which the terminal accepts output, in bytes per second
+    throughput: Option<f32>,
 }
 
 impl FrameSync {
-    pub fn new(fps: f32) -> Self {
+    pub fn new(min_fps: f32, max_fps: f32) -> Self {
+        let duration = |fps: f32| Duration::from_micros((1_000_000.0 / fps) as u64);
+        let min_duration = duration(max_fps);
+
         Self {
             render_start: None,
-            frame_duration: Duration::from_micros((1_000_000.0 / fps) as u64),
+            frame_duration: min_duration,
+            min_duration,
+            max_duration: duration(min_fps.min(max_fps)).max(min_duration),
+            flush: 0.0,
+            bytes: 0.0,
+            throughput: None,
         }
     }
 
@@ -19,6 +52,53 @@ impl FrameSync {
         self.render_start = Some(Instant::now());
     }
 
+    /// Skip a frame, postponing the render to the next deadline
+    pub fn skip(&mut self) {
+        self.start()
+    }
+
+    /// Whether the terminal has not consumed the previous frames yet.
+    ///
+    /// `pending_bytes` only counts the output queued by the local pty, data
+    /// buffered further away, such as by an SSH connection, is not visible
+    /// here and only slows frames down through the time spent flushing them.
+    pub fn is_behind(&self, pending_bytes: usize) -> bool {
+        pending_bytes as f32 > self.bytes.max(BACKLOG_BYTES)
+    }
+
+    /// Adapt the frame rate to the time it took to write the last frame
+    pub fn end(&mut self, stats: FrameStats) {
+        let average = |average: f32, value: f32| average + (value - average) * SMOOTHING;
+        let flush = stats.flush.as_secs_f32();
+
+        self.flush = average(self.flush, flush);
+        self.bytes = average(self.bytes, stats.bytes as f32);
+
+        // Only frames blocking on output tell us how fast the terminal is
+        if flush > 0.001 {
+            let throughput = stats.bytes as f32 / flush;
+
+            self.throughput = Some(match self.throughput {
+                None => throughput,
+                Some(previous) => average(previous, throughput),
+            });
+        }
+
+        // Time needed to write an average frame
+        let write = match self.throughput {
+            Some(throughput) => self.flush.max(self.bytes / throughput),
+            None => self.flush,
+        };
+
+        self.frame_duration = Duration::from_secs_f32(write / WRITE_BUDGET)
+            .clamp(self.min_duration, self.max_duration);
+    }
+
+    /// Get the current frame rate
+    pub fn fps(&self) -> f32 {
+        1.0 / self.frame_duration.as_secs_f32()
+    }
+
     /// Get a deadline until the next frame
     pub fn deadline(&self) -> Instant {
         match self.render_start {
@@ -32,3 +112,52 @@ impl FrameSync {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(bytes: usize, flush_ms: u64) -> FrameStats {
+        FrameStats {
+            bytes,
+            flush: Duration::from_millis(flush_ms),
+        }
+    }
+
+    #[test]
+    fn slows_down_on_slow_terminals() {
+        let mut sync = FrameSync::new(5.0, 60.0);
+
+        assert_eq!(sync.fps().round(), 60.0);
+
+        for _ in 0..50 {
+            sync.end(frame(100_000, 50));
+        }
+
+        // Writing takes 50ms, half of the frame budget
+        assert_eq!(sync.fps().round(), 10.0);
+
+        for _ in 0..50 {
+            sync.end(frame(1_000, 0));
+        }
+
+        assert_eq!(sync.fps().round(), 60.0);
+    }
+
+    #[test]
+    fn stays_within_bounds() {
+        let mut sync = FrameSync::new(5.0, 60.0);
+
+        sync.end(frame(10_000_000, 10_000));
+        assert_eq!(sync.fps().round(), 5.0);
+
+        // Minimum above the maximum, or rates too low to represent
+        for (min_fps, max_fps) in [(120.0, 60.0), (-5.0, 60.0), (0.0, 60.0)] {
+            let mut sync = FrameSync::new(min_fps, max_fps);
+
+            sync.end(frame(10_000_000, 10_000));
+            sync.end(frame(0, 0));
+            assert!(sync.fps() <= 60.5, "{min_fps} to {max_fps}");
+        }
+    }
+}
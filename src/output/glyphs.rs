@@ -0,0 +1,75 @@
+use std::str::FromStr;
+
+use crate::gfx::Color;
+
+/// Set of glyphs used by the CPU renderer to approximate a cell of pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GlyphSet {
+    /// 2x2 quadrant blocks
+    Quadrant,
+    /// 2x4 Braille dots
+    Braille,
+    /// 2x3 sextant blocks
+    Sextant,
+    /// 2x4 octant blocks, introduced in Unicode 16
+    Octant,
+    /// Block elements and wedges, matched against every pixel of the cell
+    Block,
+}
+
+impl FromStr for GlyphSet {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "quadrant" => Ok(Self::Quadrant),
+            "braille" => Ok(Self::Braille),
+            "sextant" => Ok(Self::Sextant),
+            "octant" => Ok(Self::Octant),
+            "block" => Ok(Self::Block),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Split colors in two groups around their luminance midpoint.
+///
+/// Returns a mask of colors in the brightest group, and the average color of each group.
+pub fn split_colors(colors: &[Color]) -> (u32, Color, Color) {
+    const LUMA: Color<f32> = Color::new(0.299, 0.587, 0.114);
+    let luma = |color: &Color| LUMA.dot(color.cast());
+
+    let (min, max) = colors
+        .iter()
+        .map(luma)
+        .fold((f32::MAX, f32::MIN), |(min, max), l| {
+            (min.min(l), max.max(l))
+        });
+    let mid = min + (max - min) / 2.0;
+    let mut mask = 0;
+    let (mut dark, mut bright) = (Vec::with_capacity(colors.len()), Vec::new());
+
+    for (index, color) in colors.iter().enumerate() {
+        if luma(color) > mid {
+            mask |= 1 << index;
+            bright.push(*color);
+        } else {
+            dark.push(*color);
+        }
+    }
+
+    (mask, average(&dark), average(&bright))
+}
+
+/// Average a set of colors
+pub fn average(colors: &[Color]) -> Color {
+    if colors.is_empty() {
+        return Color::black();
+    }
+
+    let sum = colors.iter().fold(Color::<u32>::splat(0), |sum, color| {
+        sum + color.cast::<u32>()
+    });
+
+    (sum / colors.len() as u32).cast()
+}
@@ -0,0 +1,409 @@
+use std::{io, str::FromStr};
+
+use crate::gfx::{Color, Point, Size};
+
+use super::{
+    iterm, kitty,
+    quantizer::{histogram, quantize, Palette},
+    sixel, Painter,
+};
+
+/// Size in cells of the tiles sent as separate images
+const TILE_SIZE: Size<usize> = Size::new(16, 8);
+/// Sixel palettes are built again once more than `1 / NEW_COLORS` of the pixels
+/// of a damaged region have colors missing from the region it was built from
+const NEW_COLORS: u32 = 16;
+
+/// Terminal graphics protocol used to draw the page as pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphicsProtocol {
+    Sixel,
+    Kitty,
+    Iterm,
+}
+
+impl GraphicsProtocol {
+    /// Whether support needs to be confirmed by the terminal before use
+    pub fn needs_query(&self) -> bool {
+        match self {
+            GraphicsProtocol::Sixel => false,
+            GraphicsProtocol::Kitty | GraphicsProtocol::Iterm => true,
+        }
+    }
+
+    /// Whether images are drawn below text instead of replacing cells
+    pub fn draws_below_text(&self) -> bool {
+        match self {
+            GraphicsProtocol::Sixel | GraphicsProtocol::Iterm => false,
+            GraphicsProtocol::Kitty => true,
+        }
+    }
+
+    /// Whether a terminal name reported using XTGETTCAP supports the protocol
+    pub fn supported_by(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+
+        match self {
+            GraphicsProtocol::Iterm => name.contains("iterm") || name.contains("wezterm"),
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for GraphicsProtocol {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "sixel" => Ok(Self::Sixel),
+            "kitty" => Ok(Self::Kitty),
+            "iterm" => Ok(Self::Iterm),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A region of the page in cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Damage {
+    pub top: usize,
+    pub left: usize,
+    pub right: usize,
+    pub bottom: usize,
+}
+
+impl Damage {
+    pub fn union(&self, other: Damage) -> Damage {
+        Damage {
+            top: self.top.min(other.top),
+            left: self.left.min(other.left),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.left && x < self.right && y >= self.top && y < self.bottom
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left >= self.right || self.top >= self.bottom
+    }
+
+    pub fn size(&self) -> Size<usize> {
+        Size::new(self.right - self.left, self.bottom - self.top)
+    }
+}
+
+/// Draws damaged regions of the page pixels as images.
+pub struct Graphics {
+    protocol: GraphicsProtocol,
+    /// Whether the terminal supports the protocol
+    enabled: bool,
+    /// Whether previous images should be removed from the terminal
+    clear: bool,
+    /// Size of the page in cells
+    size: Size<usize>,
+    /// Size of a terminal cell in device pixels, guessed until reported by the terminal
+    cell_size: Size<usize>,
+    /// Region of the page that needs to be drawn again
+    damage: Option<Damage>,
+    /// Palette of sixel images, with the histogram of the colors it was built from
+    palette: Option<(Palette, Vec<u32>)>,
+}
+
+impl Graphics {
+    pub fn new(protocol: GraphicsProtocol) -> Self {
+        Self {
+            protocol,
+            enabled: !protocol.needs_query(),
+            clear: false,
+            size: Size::splat(0),
+            cell_size: Size::new(8, 16),
+            damage: None,
+            palette: None,
+        }
+    }
+
+    pub fn protocol(&self) -> GraphicsProtocol {
+        self.protocol
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Start drawing images, called when the terminal confirmed support
+    pub fn enable(&mut self) {
+        self.enabled = true;
+        self.clear = true;
+        self.damage_all();
+    }
+
+    pub fn set_size(&mut self, size: Size<usize>) {
+        self.size = size;
+        self.clear = true;
+        self.damage_all();
+    }
+
+    pub fn set_cell_size(&mut self, cell_size: Size<usize>) {
+        if cell_size.width > 0 && cell_size.height > 0 && cell_size != self.cell_size {
+            self.cell_size = cell_size;
+            self.damage_all();
+        }
+    }
+
+    /// Mark the whole page as damaged
+    pub fn damage_all(&mut self) {
+        self.palette = None;
+        self.damage(Damage {
+            top: 0,
+            left: 0,
+            right: self.size.width,
+            bottom: self.size.height,
+        })
+    }
+
+    /// Mark a region of the page as damaged
+    pub fn damage(&mut self, damage: Damage) {
+        if damage.is_empty() {
+            return;
+        }
+
+        self.damage = Some(match self.damage {
+            None => damage,
+            Some(previous) => previous.union(damage),
+        })
+    }
+
+    /// Get the damaged region and reset it
+    pub fn take_damage(&mut self) -> Option<Damage> {
+        self.damage.take()
+    }
+
+    /// Draw a damaged region of the page using the terminal graphics protocol,
+    /// `frame` being the page pixels encoded in BGRA8888, 4x8 pixels per cell.
+    ///
+    /// Kitty and iTerm2 images are sent at that resolution and scaled to their cells
+    /// by the terminal, sixel images are scaled up to the cell size in device pixels.
+    ///
+    /// Returns the region where text has been overwritten and should be painted again.
+    pub fn paint(
+        &mut self,
+        damage: Damage,
+        frame: &[u8],
+        painter: &mut Painter,
+    ) -> io::Result<Option<Damage>> {
+        let mut image = Vec::new();
+        let cursor = |x: usize, y: usize| Point::new(x as u32, y as u32 + 1);
+
+        match self.protocol {
+            GraphicsProtocol::Sixel => {
+                let (mut colors, width) = self.region(frame, damage);
+                let height = colors.len() / width.max(1);
+                let scaled = self.scaled_size(damage);
+                let histogram = histogram(&colors);
+                let palette = match self.palette.take() {
+                    Some((palette, source)) if !has_new_colors(&histogram, &source) => {
+                        (palette, source)
+                    }
+                    _ => (quantize(&mut colors.clone()), histogram),
+                };
+                let (palette, _) = self.palette.insert(palette);
+                let indices = colors
+                    .drain(..)
+                    .map(|color| palette.index_of(color))
+                    .collect::<Vec<_>>();
+                let indices = scale(&indices, Size::new(width, height), scaled);
+
+                sixel::encode(&mut image, palette, &indices, scaled.width, scaled.height)?;
+                painter.image(cursor(damage.left, damage.top), &image)?;
+
+                Ok(Some(damage))
+            }
+            GraphicsProtocol::Kitty => {
+                let cleared = std::mem::take(&mut self.clear);
+
+                if cleared {
+                    // Remove previous images and any glyph left on the page
+                    kitty::clear(&mut image)?;
+                    image.extend_from_slice(b"\x1b[J");
+                    painter.image(cursor(0, 0), &image)?;
+                }
+
+                // Upload every tile intersecting the damage
+                for (id, tile) in self.tiles(damage) {
+                    let (rgb, size) = self.region_rgb(frame, tile);
+
+                    image.clear();
+                    kitty::encode(&mut image, id, &rgb, size, tile.size())?;
+                    painter.image(cursor(tile.left, tile.top), &image)?;
+                }
+
+                // Text is drawn on top of images, unless we erased it
+                Ok(if cleared { Some(damage) } else { None })
+            }
+            GraphicsProtocol::Iterm => {
+                let mut overwritten = damage;
+
+                for (_, tile) in self.tiles(damage) {
+                    let (rgb, size) = self.region_rgb(frame, tile);
+
+                    image.clear();
+                    iterm::encode(&mut image, &rgb, size, tile.size())?;
+                    painter.image(cursor(tile.left, tile.top), &image)?;
+                    overwritten = overwritten.union(tile);
+                }
+
+                Ok(Some(overwritten))
+            }
+        }
+    }
+
+    /// Get the tiles intersecting a damaged region, along with an unique identifier
+    fn tiles(&self, damage: Damage) -> Vec<(usize, Damage)> {
+        let columns = self.size.width.div_ceil(TILE_SIZE.width);
+        let mut tiles = Vec::new();
+
+        for y in (damage.top / TILE_SIZE.height)..damage.bottom.div_ceil(TILE_SIZE.height) {
+            for x in (damage.left / TILE_SIZE.width)..damage.right.div_ceil(TILE_SIZE.width) {
+                tiles.push((
+                    1 + y * columns + x,
+                    Damage {
+                        top: y * TILE_SIZE.height,
+                        left: x * TILE_SIZE.width,
+                        right: ((x + 1) * TILE_SIZE.width).min(self.size.width),
+                        bottom: ((y + 1) * TILE_SIZE.height).min(self.size.height),
+                    },
+                ))
+            }
+        }
+
+        tiles
+    }
+
+    /// Get the pixels of a region of the page encoded in RGB888, and its size
+    fn region_rgb(&self, frame: &[u8], damage: Damage) -> (Vec<u8>, Size<usize>) {
+        let (colors, width) = self.region(frame, damage);
+        let height = colors.len() / width.max(1);
+        let rgb = colors.iter().flat_map(|color| color.to_array()).collect();
+
+        (rgb, Size::new(width, height))
+    }
+
+    /// Size in device pixels of a region of cells.
+    ///
+    /// Sixel images are drawn in bands of 6 pixels, the height of regions reaching
+    /// the bottom of the page is rounded down so the last band does not go past
+    /// the last row, which would make the terminal scroll.
+    fn scaled_size(&self, damage: Damage) -> Size<usize> {
+        let size = damage.size() * self.cell_size;
+
+        if damage.bottom >= self.size.height {
+            Size::new(size.width, size.height / 6 * 6)
+        } else {
+            size
+        }
+    }
+
+    /// Get the colors of a region of the page, and its width in pixels
+    fn region(&self, frame: &[u8], damage: Damage) -> (Vec<Color>, usize) {
+        let row_length = self.size.width * 4;
+        let width = (damage.right - damage.left) * 4;
+        let mut colors = Vec::with_capacity(width * (damage.bottom - damage.top) * 8);
+
+        for y in (damage.top * 8)..(damage.bottom * 8) {
+            let start = (y * row_length + damage.left * 4) * 4;
+            let mut iter = frame[start..start + width * 4].iter();
+
+            while let Some(color) = Color::from_iter(&mut iter) {
+                colors.push(color)
+            }
+        }
+
+        (colors, width)
+    }
+}
+
+/// Copy a region of a BGRA8888 pixel array to a frame of `size` cells, 4x8 pixels per cell.
+///
+/// Returns `false` if the region is out of bounds.
+pub fn copy_pixels(
+    frame: &mut [u8],
+    size: Size<usize>,
+    pixels: &[u8],
+    pixels_size: Size<usize>,
+    damage: Damage,
+) -> bool {
+    let row_bytes = size.width * 4 * 4;
+    let src_row_bytes = pixels_size.width * 4;
+    let (start, end) = (damage.left * 4 * 4, damage.right * 4 * 4);
+
+    if end > src_row_bytes || end > row_bytes {
+        return false;
+    }
+
+    for y in (damage.top * 8)..(damage.bottom * 8) {
+        let src = y * src_row_bytes;
+        let dst = y * row_bytes;
+
+        if src + end > pixels.len() || dst + end > frame.len() {
+            break;
+        }
+
+        frame[dst + start..dst + end].copy_from_slice(&pixels[src + start..src + end]);
+    }
+
+    true
+}
+
+/// Whether a histogram has many colors in bins left empty in the `source` histogram
+fn has_new_colors(histogram: &[u32], source: &[u32]) -> bool {
+    let total = histogram.iter().sum::<u32>();
+    let new = histogram
+        .iter()
+        .zip(source)
+        .filter(|(_, &source)| source == 0)
+        .map(|(&count, _)| count)
+        .sum::<u32>();
+
+    new * NEW_COLORS > total
+}
+
+/// Scale an image using nearest-neighbor sampling
+fn scale<T: Copy>(pixels: &[T], from: Size<usize>, to: Size<usize>) -> Vec<T> {
+    let mut scaled = Vec::with_capacity(to.width * to.height);
+
+    for y in 0..to.height {
+        let row = y * from.height / to.height * from.width;
+
+        for x in 0..to.width {
+            scaled.push(pixels[row + x * from.width / to.width])
+        }
+    }
+
+    scaled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_sixel_images_above_the_last_row() {
+        let mut graphics = Graphics::new(GraphicsProtocol::Sixel);
+        let rows = |top, bottom| Damage {
+            top,
+            left: 0,
+            right: 10,
+            bottom,
+        };
+
+        graphics.set_size(Size::new(10, 5));
+        graphics.set_cell_size(Size::new(9, 17));
+
+        assert_eq!(graphics.scaled_size(rows(0, 2)), Size::new(90, 34));
+        // 34 pixels would take 6 bands of 6 pixels
+        assert_eq!(graphics.scaled_size(rows(3, 5)), Size::new(90, 30));
+    }
+}
@@ -0,0 +1,30 @@
+use std::io::{self, Write};
+
+use crate::{
+    gfx::Size,
+    utils::{base64, png},
+};
+
+/// Draw an RGB image as an inline PNG file at the cursor position, scaled to the given cells
+pub fn encode<W: Write>(
+    out: &mut W,
+    rgb: &[u8],
+    size: Size<usize>,
+    cells: Size<usize>,
+) -> io::Result<()> {
+    let mut file = Vec::new();
+    let mut payload = Vec::new();
+
+    png::encode(rgb, size.width, size.height, &mut file);
+    base64::encode(&file, &mut payload);
+
+    write!(
+        out,
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=0;doNotMoveCursor=1:",
+        file.len(),
+        cells.width,
+        cells.height,
+    )?;
+    out.write_all(&payload)?;
+    out.write_all(b"\x07")
+}
@@ -1,7 +1,30 @@
-use std::ops::Mul;
-
 use crate::gfx::Color;
 
+/// A KD-tree of colors for nearest neighbor searches.
+#[derive(Clone)]
+pub struct KDTree {
+    root: Option<Box<KDNode>>,
+}
+
+impl KDTree {
+    /// Build a tree from colors identified by their index
+    pub fn new(colors: &[Color<f64>]) -> Self {
+        let mut points = colors.iter().copied().enumerate().collect::<Vec<_>>();
+
+        Self {
+            root: KDNode::new(&mut points),
+        }
+    }
+
+    /// Find the index of the nearest color using the euclidean distance
+    pub fn nearest(&self, color: Color<f64>) -> Option<usize> {
+        let (index, _) = self.root.as_ref()?.nearest(color, f64::MAX)?;
+
+        Some(index)
+    }
+}
+
+#[derive(Clone)]
 struct KDNode {
     left: Option<Box<KDNode>>,
     right: Option<Box<KDNode>>,
@@ -10,56 +33,101 @@ struct KDNode {
 }
 
 impl KDNode {
-    fn new(colors: &[Color]) {
-        let (sum, sum_squared) = colors.iter().fold(
-            (Color::black(), Color::black()),
-            |(sum, sum_squared), color| (sum + color, sum_squared + color * color),
+    fn new(points: &mut [(usize, Color<f64>)]) -> Option<Box<KDNode>> {
+        if points.is_empty() {
+            return None;
+        }
+
+        // Split along the axis with the largest variance
+        let count = points.len() as f64;
+        let (sum, sum_squared) = points.iter().fold(
+            (Color::splat(0.0), Color::splat(0.0)),
+            |(sum, sum_squared), (_, color)| (sum + *color, sum_squared + *color * *color),
         );
+        let mean = sum / count;
+        let variance = sum_squared / count - mean * mean;
+        let normal = if variance.r >= variance.g && variance.r >= variance.b {
+            Color::new(1.0, 0.0, 0.0)
+        } else if variance.g >= variance.b {
+            Color::new(0.0, 1.0, 0.0)
+        } else {
+            Color::new(0.0, 0.0, 1.0)
+        };
+
+        points.sort_unstable_by(|(_, a), (_, b)| a.dot(normal).total_cmp(&b.dot(normal)));
+
+        let (left, rest) = points.split_at_mut(points.len() / 2);
+        let (middle, right) = rest.split_first_mut()?;
+
+        Some(Box::new(KDNode {
+            left: KDNode::new(left),
+            right: KDNode::new(right),
+            normal,
+            middle: *middle,
+        }))
     }
 
+    /// Find the nearest color closer than `limit`, along with its distance
     fn nearest(&self, color: Color<f64>, mut limit: f64) -> Option<(usize, f64)> {
         let diff = color - self.middle.1;
-        let distance = diff.mul(&diff).sum().sqrt();
+        let distance = diff.dot(diff).sqrt();
         let mut result = None;
 
         if distance < limit {
             limit = distance;
+            result = Some((self.middle.0, distance));
         }
 
-        let dot = diff.mul(self.normal).sum();
+        let dot = diff.dot(self.normal);
+        let (near, far) = if dot <= 0.0 {
+            (&self.left, &self.right)
+        } else {
+            (&self.right, &self.left)
+        };
 
-        if dot <= 0.0 {
-            if let Some(ref left) = self.left {
-                if let Some(nearest) = left.nearest(color, limit) {
-                    limit = nearest.1;
-                    result = Some(nearest);
-                }
-            }
+        // Search the side of the splitting plane containing the color first
+        if let Some(nearest) = near.as_ref().and_then(|node| node.nearest(color, limit)) {
+            limit = nearest.1;
+            result = Some(nearest);
+        }
 
-            if -dot < limit {
-                if let Some(ref right) = self.right {
-                    if let Some(nearest) = right.nearest(color, limit) {
-                        result = Some(nearest);
-                    }
-                }
-            }
-        } else {
-            if let Some(ref right) = self.right {
-                if let Some(nearest) = right.nearest(color, limit) {
-                    limit = nearest.1;
-                    result = Some(nearest);
-                }
+        // The other side can only contain a nearer color if the plane is closer than it
+        if dot.abs() < limit {
+            if let Some(nearest) = far.as_ref().and_then(|node| node.nearest(color, limit)) {
+                result = Some(nearest);
             }
+        }
 
-            if dot < limit {
-                if let Some(ref left) = self.left {
-                    if let Some(nearest) = left.nearest(color, limit) {
-                        result = Some(nearest);
-                    }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_nearest_xterm_color() {
+        let palette = (0..=255)
+            .map(|code| Color::from_xterm(code).to_oklab())
+            .collect::<Vec<_>>();
+        let tree = KDTree::new(&palette);
+        let distance = |a: Color<f64>, b: Color<f64>| (a - b).dot(a - b);
+
+        for r in (0..=255).step_by(17) {
+            for g in (0..=255).step_by(17) {
+                for b in (0..=255).step_by(17) {
+                    let color = Color::new(r as u8, g as u8, b as u8).to_oklab();
+                    let nearest = tree.nearest(color).unwrap();
+                    // The palette contains duplicates, compare distances
+                    let expected = palette
+                        .iter()
+                        .map(|&candidate| distance(color, candidate))
+                        .fold(f64::MAX, f64::min);
+
+                    assert_eq!(distance(color, palette[nearest]), expected, "{r},{g},{b}");
                 }
             }
         }
-
-        result
     }
 }
@@ -0,0 +1,91 @@
+use std::io::{self, Write};
+
+use crate::{gfx::Size, utils::base64};
+
+/// Maximum size of the base64 payload in a single escape sequence
+const CHUNK_SIZE: usize = 4096;
+
+/// Transmit an RGB image and place it below the text at the cursor position.
+///
+/// Transmitting an image with an existing `id` replaces it.
+pub fn encode<W: Write>(
+    out: &mut W,
+    id: usize,
+    rgb: &[u8],
+    size: Size<usize>,
+    cells: Size<usize>,
+) -> io::Result<()> {
+    let mut payload = Vec::new();
+
+    base64::encode(rgb, &mut payload);
+
+    let mut chunks = payload.chunks(CHUNK_SIZE).peekable();
+    let mut first = true;
+
+    while let Some(chunk) = chunks.next() {
+        let more = chunks.peek().is_some() as u8;
+
+        if first {
+            first = false;
+
+            // Raw RGB pixels scaled to the given cells, placed below text,
+            // without moving the cursor and without any response.
+            write!(
+                out,
+                "\x1b_Ga=T,f=24,q=2,C=1,z=-1,i={id},p=1,s={},v={},c={},r={},m={more};",
+                size.width, size.height, cells.width, cells.height,
+            )?;
+        } else {
+            write!(out, "\x1b_Gm={more};")?;
+        }
+
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+
+    Ok(())
+}
+
+/// Delete every image and placement
+pub fn clear<W: Write>(out: &mut W) -> io::Result<()> {
+    out.write_all(b"\x1b_Ga=d,d=A,q=2\x1b\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(rgb: &[u8]) -> Vec<String> {
+        let mut out = Vec::new();
+
+        encode(&mut out, 1, rgb, Size::new(32, 32), Size::new(8, 4)).unwrap();
+
+        String::from_utf8(out)
+            .unwrap()
+            .split_terminator("\x1b\\")
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn sends_a_single_chunk() {
+        // Encoded in exactly one chunk of base64
+        let chunks = chunks(&[0; CHUNK_SIZE / 4 * 3]);
+
+        assert_eq!(chunks.len(), 1);
+        assert!(
+            chunks[0].starts_with("\x1b_Ga=T,f=24,q=2,C=1,z=-1,i=1,p=1,s=32,v=32,c=8,r=4,m=0;AAAA")
+        );
+        assert_eq!(chunks[0].split_once(';').unwrap().1.len(), CHUNK_SIZE);
+    }
+
+    #[test]
+    fn splits_chunks() {
+        let chunks = chunks(&[0; CHUNK_SIZE / 4 * 3 + 3]);
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].contains(",m=1;"));
+        assert_eq!(chunks[0].split_once(';').unwrap().1.len(), CHUNK_SIZE);
+        assert_eq!(chunks[1], "\x1b_Gm=0;AAAA");
+    }
+}
@@ -1,50 +1,283 @@
-use std::io::{self, Stdout, Write};
+use std::{
+    cell::{RefCell, RefMut},
+    io::{self, Write},
+    rc::Rc,
+};
 
-use crate::gfx::{Color, Point};
+use crate::{
+    gfx::{Color, Point, Size},
+    utils::{
+        log,
+        terminfo::{self, Terminfo},
+    },
+};
 
-use super::Cell;
+use super::{Cell, ColorDepth, FrameSink, XtermPalette};
+
+/// Maximum size of a frame split for terminals without synchronized updates,
+/// small enough for the terminal to parse it at once instead of drawing it in pieces
+const UNSYNCHRONIZED_BUDGET: usize = 16 * 1024;
 
 pub struct Painter {
-    output: Stdout,
+    output: Box<dyn FrameSink>,
+    /// Whether frames are written to the standard output
+    stdout: bool,
+    /// Sinks receiving a copy of the frames, dropped on error
+    mirrors: Vec<Box<dyn FrameSink>>,
+    /// Window title, sent again to mirrors joining the session
+    title: Option<String>,
     buffer: Vec<u8>,
+    /// Color sequences of the cell being painted
+    sgr: Vec<u8>,
+    /// Identical cells waiting to be written
+    run: Option<Run>,
     cursor: Option<Point<u32>>,
-    true_color: bool,
+    /// Size of the terminal in cells
+    size: Size,
+    /// Whether erased cells use the current background color
+    erase: bool,
+    /// Whether the terminal supports repeating characters using REP
+    repeat: bool,
+    /// Whether frames are wrapped in synchronized updates
+    synchronized: bool,
+    /// Whether frames are split under a budget until the terminal reports
+    /// synchronized output support, enabled unless overridden
+    split_frames: bool,
+    depth: ColorDepth,
+    /// Palette shared with forked painters, along with its lookup caches
+    palette: Rc<RefCell<XtermPalette>>,
     background: Option<Color>,
     foreground: Option<Color>,
     background_code: Option<u8>,
     foreground_code: Option<u8>,
+    reverse: Option<bool>,
+}
+
+/// A sequence of identical cells painted on a row.
+struct Run {
+    cursor: Point<u32>,
+    count: u32,
+    char: char,
+    /// Whether the cells can be drawn using erase sequences
+    erase: bool,
+}
+
+impl Default for Painter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Painter {
     pub fn new() -> Painter {
+        Painter {
+            stdout: true,
+            ..Self::with_output(Box::new(io::stdout()))
+        }
+    }
+
+    /// Create a painter writing frames to a custom output
+    pub fn with_output(output: Box<dyn FrameSink>) -> Painter {
+        let term = std::env::var("TERM").unwrap_or_default();
+        let info = Terminfo::load(&term);
+        let erase = info
+            .as_ref()
+            .is_none_or(|info| info.flag(terminfo::BACK_COLOR_ERASE));
+        let repeat = info
+            .as_ref()
+            .is_some_and(|info| info.string(terminfo::REPEAT_CHAR).is_some());
+
+        Self::with_capabilities(output, erase, repeat, ColorDepth::detect())
+    }
+
+    /// Create a painter for a terminal with known capabilities
+    fn with_capabilities(
+        output: Box<dyn FrameSink>,
+        erase: bool,
+        repeat: bool,
+        depth: ColorDepth,
+    ) -> Painter {
         Painter {
             buffer: Vec::new(),
+            sgr: Vec::new(),
+            run: None,
             cursor: None,
-            output: io::stdout(),
+            size: Size::splat(0),
+            erase,
+            repeat,
+            synchronized: false,
+            split_frames: true,
+            output,
+            stdout: false,
+            mirrors: Vec::new(),
+            title: None,
             background: None,
             foreground: None,
             background_code: None,
             foreground_code: None,
-            true_color: match std::env::var("COLORTERM").unwrap_or_default().as_str() {
-                "truecolor" | "24bit" => true,
-                _ => false,
-            },
+            reverse: None,
+            depth,
+            palette: Rc::new(RefCell::new(XtermPalette::new())),
         }
     }
 
-    pub fn true_color(&self) -> bool {
-        self.true_color
+    pub fn color_depth(&self) -> ColorDepth {
+        self.depth
+    }
+
+    pub fn palette(&mut self) -> RefMut<'_, XtermPalette> {
+        self.palette.borrow_mut()
     }
 
-    pub fn set_true_color(&mut self, true_color: bool) {
-        self.true_color = true_color
+    pub fn set_color_depth(&mut self, depth: ColorDepth) {
+        self.depth = depth;
+        // Colors are encoded differently between depths, force them to be set again
+        self.background_code = None;
+        self.foreground_code = None;
+        self.background = None;
+        self.foreground = None;
+        self.reverse = None;
+    }
+
+    /// Set the size of the terminal in cells
+    pub fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self.output.resize(size);
+
+        for mirror in self.mirrors.iter_mut() {
+            mirror.resize(size)
+        }
+    }
+
+    pub fn set_synchronized(&mut self, synchronized: bool) {
+        self.synchronized = synchronized
+    }
+
+    pub fn set_split_frames(&mut self, split_frames: bool) {
+        self.split_frames = split_frames
+    }
+
+    /// Send a copy of the frames to another sink
+    pub fn add_mirror(&mut self, mut mirror: Box<dyn FrameSink>) {
+        if self.size.width > 0 {
+            mirror.resize(self.size);
+        }
+
+        self.mirrors.push(mirror)
     }
 
     pub fn begin(&mut self) -> io::Result<()> {
+        if self.synchronized {
+            write!(self.buffer, "\x1b[?2026h")?;
+        }
+
         write!(self.buffer, "\x1b[?25l\x1b[?12l")
     }
 
-    pub fn end(&mut self, cursor: Option<Point>) -> io::Result<()> {
+    /// Number of bytes written to the terminal but not transmitted yet,
+    /// as queued by the local pty
+    pub fn pending_output(&self) -> usize {
+        if !self.stdout {
+            return 0;
+        }
+
+        let mut pending: libc::c_int = 0;
+
+        match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCOUTQ, &mut pending) } {
+            0 => pending.max(0) as usize,
+            _ => 0,
+        }
+    }
+
+    /// Whether more cells can be painted in the current frame.
+    /// When splitting frames without synchronized updates, frames are kept
+    /// under a budget, remaining cells being painted by the next frames.
+    pub fn has_budget(&self) -> bool {
+        !self.split_frames || self.synchronized || self.buffer.len() < UNSYNCHRONIZED_BUDGET
+    }
+
+    /// Write the frame to the terminal, returns the number of bytes written
+    pub fn end(&mut self, cursor: Option<Point>) -> io::Result<usize> {
+        self.finish(cursor)?;
+
+        let bytes = self.buffer.len();
+
+        // Write the frame at once with the cursor hidden
+        self.output.write_frame(self.buffer.as_slice())?;
+        self.mirrors
+            .retain_mut(|mirror| keep_mirror(mirror.write_frame(&self.buffer)));
+        self.buffer.clear();
+        self.cursor = None;
+
+        Ok(bytes)
+    }
+
+    /// Whether a mirror is waiting for a frame painting every cell
+    pub fn needs_full_frame(&mut self) -> bool {
+        self.mirrors
+            .iter_mut()
+            .any(|mirror| mirror.needs_full_frame())
+    }
+
+    /// Paint every cell from a blank screen, for mirrors waiting for a full frame.
+    ///
+    /// Mirrors only start from cells: the page is drawn using blocks even when the terminal
+    /// uses a graphics protocol, and images reach mirrors with the frames redrawing them.
+    pub fn paint_full_frame<'a>(
+        &mut self,
+        cells: impl IntoIterator<Item = &'a Cell>,
+        cursor: Option<Point>,
+    ) -> io::Result<()> {
+        let mut painter = self.fork();
+
+        painter.begin()?;
+        painter.buffer.write_all(b"\x1b[0m\x1b[H\x1b[2J")?;
+
+        if let Some(ref title) = self.title {
+            write_title(&mut painter.buffer, title)?;
+        }
+
+        for cell in cells {
+            painter.paint(cell)?;
+        }
+
+        painter.finish(cursor)?;
+
+        self.mirrors.retain_mut(|mirror| {
+            !mirror.needs_full_frame() || keep_mirror(mirror.write_full_frame(&painter.buffer))
+        });
+
+        Ok(())
+    }
+
+    /// Create a painter with the same settings, starting from an unknown terminal state
+    fn fork(&self) -> Painter {
+        Painter {
+            size: self.size,
+            synchronized: self.synchronized,
+            palette: Rc::clone(&self.palette),
+            ..Painter::with_capabilities(Box::new(io::sink()), self.erase, self.repeat, self.depth)
+        }
+    }
+
+    /// Set the window title of the terminal and its mirrors
+    pub fn set_title(&mut self, title: &str) -> io::Result<()> {
+        let mut sequences = Vec::new();
+
+        write_title(&mut sequences, title)?;
+
+        self.title = Some(title.to_owned());
+        self.output.write_frame(&sequences)?;
+        self.mirrors
+            .retain_mut(|mirror| keep_mirror(mirror.write_frame(&sequences)));
+
+        Ok(())
+    }
+
+    /// Write the sequences ending a frame
+    fn finish(&mut self, cursor: Option<Point>) -> io::Result<()> {
+        self.flush_run()?;
+
         if let Some(cursor) = cursor {
             write!(
                 self.buffer,
@@ -54,9 +287,20 @@ impl Painter {
             )?;
         }
 
-        self.output.write(self.buffer.as_slice())?;
-        self.output.flush()?;
-        self.buffer.clear();
+        if self.synchronized {
+            write!(self.buffer, "\x1b[?2026l")?;
+        }
+
+        Ok(())
+    }
+
+    /// Draw an image encoded using a terminal graphics protocol at a given cell
+    pub fn image(&mut self, cursor: Point<u32>, image: &[u8]) -> io::Result<()> {
+        self.flush_run()?;
+        write!(self.buffer, "\x1b[{};{}H", cursor.y + 1, cursor.x + 1)?;
+
+        self.buffer.write_all(image)?;
+        // The cursor position after an image depends on the terminal
         self.cursor = None;
 
         Ok(())
@@ -68,7 +312,8 @@ impl Painter {
             ref grapheme,
             background: cell_bg,
             foreground: cell_fg,
-            codepoint,
+            mut codepoint,
+            palette,
             ..
         } = *cell;
 
@@ -82,61 +327,358 @@ impl Painter {
             (cell_bg, cell_fg, 1)
         };
 
-        if self.cursor != Some(cursor) {
-            write!(self.buffer, "\x1b[{};{}H", cursor.y + 1, cursor.x + 1)?;
-        };
+        match self.depth {
+            ColorDepth::TrueColor => {
+                if self.background != Some(background) {
+                    self.background = Some(background);
 
-        self.cursor = Some(cursor + Point::new(width, 0));
+                    write!(
+                        self.sgr,
+                        "\x1b[48;2;{};{};{}m",
+                        background.r, background.g, background.b,
+                    )?
+                }
 
-        if self.background != Some(background) {
-            self.background = Some(background);
+                if self.foreground != Some(foreground) {
+                    self.foreground = Some(foreground);
 
-            if self.true_color {
-                write!(
-                    self.buffer,
-                    "\x1b[48;2;{};{};{}m",
-                    background.r, background.g, background.b,
-                )?
-            } else {
-                let code = background.to_xterm();
+                    write!(
+                        self.sgr,
+                        "\x1b[38;2;{};{};{}m",
+                        foreground.r, foreground.g, foreground.b,
+                    )?
+                }
+            }
+            ColorDepth::Xterm256 => {
+                // Dithered colors might use a different index for the same color
+                let codes = match palette {
+                    Some((background, _)) if grapheme.is_some() => {
+                        (background, self.palette().index_of(foreground))
+                    }
+                    Some(codes) => codes,
+                    None => {
+                        let mut palette = self.palette();
 
-                if self.background_code != Some(code) {
-                    self.background_code = Some(code);
+                        (palette.index_of(background), palette.index_of(foreground))
+                    }
+                };
 
-                    write!(self.buffer, "\x1b[48;5;{code}m")?
-                }
+                self.set_codes(codes, sgr_xterm)?
             }
-        }
+            ColorDepth::Ansi16 => {
+                let codes = {
+                    let mut palette = self.palette.borrow_mut();
+
+                    (
+                        palette.ansi_index_of(background),
+                        palette.ansi_index_of(foreground),
+                    )
+                };
 
-        if self.foreground != Some(foreground) {
-            self.foreground = Some(foreground);
+                self.set_codes(codes, sgr_ansi)?
+            }
+            ColorDepth::Mono => {
+                let defaults = {
+                    let palette = self.palette.borrow();
 
-            if self.true_color {
-                write!(
-                    self.buffer,
-                    "\x1b[38;2;{};{};{}m",
-                    foreground.r, foreground.g, foreground.b,
-                )?
-            } else {
-                let code = foreground.to_xterm();
+                    (palette.background(), palette.foreground())
+                };
+                let reverse = if grapheme.is_some() {
+                    // Draw text using the default color closest to the page background
+                    coverage(background, defaults) > 0.5
+                } else {
+                    let (reverse, glyph) = mono_glyph(background, foreground, codepoint, defaults);
 
-                if self.foreground_code != Some(code) {
-                    self.foreground_code = Some(code);
+                    codepoint = glyph;
+                    reverse
+                };
 
-                    write!(self.buffer, "\x1b[38;5;{code}m")?
+                if self.reverse != Some(reverse) {
+                    self.reverse = Some(reverse);
+                    self.sgr
+                        .write_all(if reverse { b"\x1b[7m" } else { b"\x1b[27m" })?
                 }
             }
         }
 
+        let char = char::from_u32(codepoint).unwrap_or(' ');
+
+        // Extend the current run if the cell has the same content and colors
+        if let (None, true, Some(run)) = (grapheme, self.sgr.is_empty(), &mut self.run) {
+            if run.char == char && run.cursor + Point::new(run.count, 0) == cursor {
+                run.count += 1;
+
+                return Ok(());
+            }
+        }
+
+        self.flush_run()?;
+        self.move_to(cursor)?;
+        self.buffer.append(&mut self.sgr);
+
         if let Some(grapheme) = grapheme {
             self.buffer.write_all(grapheme.char.as_bytes())?;
+            self.cursor = Some(cursor + Point::new(width, 0));
+        } else {
+            self.run = Some(Run {
+                cursor,
+                count: 1,
+                char,
+                erase: self.erase && self.reverse != Some(true),
+            })
+        }
+
+        Ok(())
+    }
+
+    /// Write the pending run of cells using the shortest sequence
+    fn flush_run(&mut self) -> io::Result<()> {
+        let Some(Run {
+            cursor,
+            count,
+            char,
+            erase,
+        }) = self.run.take()
+        else {
+            return Ok(());
+        };
+        let end = cursor + Point::new(count, 0);
+
+        // Erasing cells does not move the cursor
+        if char == ' ' && erase {
+            if self.size.width > 0 && end.x >= self.size.width && count > 3 {
+                self.cursor = Some(cursor);
+
+                return self.buffer.write_all(b"\x1b[K");
+            }
+
+            // Moving the cursor after the erased cells can cost as much as erasing
+            if csi_len(count) * 2 < count as usize {
+                self.cursor = Some(cursor);
+
+                return csi(&mut self.buffer, count, b'X');
+            }
+        }
+
+        let mut utf8 = [0u8; 4];
+        let bytes = char.encode_utf8(&mut utf8).as_bytes();
+        let repeat = count - 1;
+
+        self.buffer.write_all(bytes)?;
+        self.cursor = Some(end);
+
+        if self.repeat && repeat > 0 && csi_len(repeat) < repeat as usize * bytes.len() {
+            csi(&mut self.buffer, repeat, b'b')
         } else {
-            let mut buf = [0u8; 4];
-            let ch = char::from_u32(codepoint).unwrap_or(' ');
-            let encoded = ch.encode_utf8(&mut buf);
-            self.buffer.write_all(encoded.as_bytes())?;
+            for _ in 0..repeat {
+                self.buffer.write_all(bytes)?;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Move the cursor using the shortest of relative or absolute sequences
+    fn move_to(&mut self, target: Point<u32>) -> io::Result<()> {
+        let (x, y) = (target.x + 1, target.y + 1);
+        let absolute = 4 + digits(x) + digits(y);
+        let relative = match self.cursor {
+            Some(cursor) if cursor == target => return Ok(()),
+            // Writing to the last column leaves the cursor in place
+            Some(cursor) if cursor.x >= self.size.width => None,
+            Some(cursor) if cursor.y == target.y && cursor.x < target.x => {
+                Some((target.x - cursor.x, b'C'))
+            }
+            Some(cursor) if cursor.y == target.y => Some((cursor.x - target.x, b'D')),
+            Some(cursor) if cursor.x == target.x && cursor.y < target.y => {
+                Some((target.y - cursor.y, b'B'))
+            }
+            Some(cursor) if cursor.x == target.x => Some((cursor.y - target.y, b'A')),
+            _ => None,
+        };
+
+        self.cursor = Some(target);
+
+        match relative {
+            Some((count, code)) if csi_len(count) < absolute => csi(&mut self.buffer, count, code),
+            _ => write!(self.buffer, "\x1b[{y};{x}H"),
+        }
+    }
+
+    /// Set palette colors, `sgr` writing the sequence selecting a background or foreground index
+    fn set_codes(
+        &mut self,
+        (background, foreground): (u8, u8),
+        sgr: fn(&mut Vec<u8>, u8, bool) -> io::Result<()>,
+    ) -> io::Result<()> {
+        if self.background_code != Some(background) {
+            self.background_code = Some(background);
+
+            sgr(&mut self.sgr, background, true)?
+        }
+
+        if self.foreground_code != Some(foreground) {
+            self.foreground_code = Some(foreground);
+
+            sgr(&mut self.sgr, foreground, false)?
         }
 
         Ok(())
     }
 }
+
+/// Whether a mirror can still be used after writing a frame to it
+fn keep_mirror(result: io::Result<()>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(error) => {
+            log::debug!("mirror: {}", error);
+
+            false
+        }
+    }
+}
+
+/// Write the sequences setting the icon and window titles
+fn write_title(out: &mut Vec<u8>, title: &str) -> io::Result<()> {
+    write!(out, "\x1b]0;{title}\x07")?;
+    write!(out, "\x1b]1;{title}\x07")?;
+    write!(out, "\x1b]2;{title}\x07")
+}
+
+/// Write a control sequence with a count parameter, omitted if 1
+fn csi(out: &mut Vec<u8>, count: u32, code: u8) -> io::Result<()> {
+    if count == 1 {
+        out.write_all(&[0x1b, b'[', code])
+    } else {
+        write!(out, "\x1b[{count}{}", code as char)
+    }
+}
+
+/// Length of a control sequence written using `csi`
+fn csi_len(count: u32) -> usize {
+    if count == 1 {
+        3
+    } else {
+        3 + digits(count)
+    }
+}
+
+fn digits(value: u32) -> usize {
+    value.checked_ilog10().unwrap_or(0) as usize + 1
+}
+
+fn sgr_xterm(out: &mut Vec<u8>, code: u8, background: bool) -> io::Result<()> {
+    write!(out, "\x1b[{};5;{code}m", if background { 48 } else { 38 })
+}
+
+fn sgr_ansi(out: &mut Vec<u8>, code: u8, background: bool) -> io::Result<()> {
+    // Bright colors use a separate range of parameters
+    let base = match (background, code < 8) {
+        (false, true) => 30,
+        (false, false) => 90 - 8,
+        (true, true) => 40,
+        (true, false) => 100 - 8,
+    };
+
+    write!(out, "\x1b[{}m", base + code as u32)
+}
+
+/// Pick a glyph for a cell using only the default colors.
+///
+/// Returns whether colors should be reversed, and the code point to draw.
+fn mono_glyph(
+    background: Color,
+    foreground: Color,
+    codepoint: u32,
+    defaults: (Color, Color),
+) -> (bool, u32) {
+    const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+    let (background, foreground) = (
+        coverage(background, defaults),
+        coverage(foreground, defaults),
+    );
+
+    if (background > 0.5) != (foreground > 0.5) {
+        // Keep the glyph shape, reversing colors for backgrounds close to the default foreground
+        (background > 0.5, codepoint)
+    } else {
+        let coverage = (background + foreground) / 2.0;
+        let shade = (coverage * SHADES.len() as f32) as usize;
+
+        (false, SHADES[shade.min(SHADES.len() - 1)] as u32)
+    }
+}
+
+/// Fraction of the default foreground over the default background needed to match a color
+fn coverage(color: Color, (background, foreground): (Color, Color)) -> f32 {
+    let (dark, light) = (background.luminance() as f32, foreground.luminance() as f32);
+    let luminance = color.luminance() as f32;
+
+    if (light - dark).abs() < 1.0 {
+        luminance / 255.0
+    } else {
+        ((luminance - dark) / (light - dark)).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sgr(code: u8, background: bool) -> String {
+        let mut out = Vec::new();
+
+        sgr_ansi(&mut out, code, background).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_dark_and_bright_ansi_colors() {
+        assert_eq!(sgr(0, false), "\x1b[30m");
+        assert_eq!(sgr(7, false), "\x1b[37m");
+        assert_eq!(sgr(8, false), "\x1b[90m");
+        assert_eq!(sgr(15, false), "\x1b[97m");
+        assert_eq!(sgr(0, true), "\x1b[40m");
+        assert_eq!(sgr(7, true), "\x1b[47m");
+        assert_eq!(sgr(8, true), "\x1b[100m");
+        assert_eq!(sgr(15, true), "\x1b[107m");
+    }
+
+    #[test]
+    fn maps_coverage_to_mono_glyphs() {
+        let (black, white, gray) = (Color::black(), Color::splat(255), Color::splat(128));
+        let defaults = (black, white);
+        let glyph = 'x' as u32;
+
+        assert_eq!(mono_glyph(black, white, glyph, defaults), (false, glyph));
+        assert_eq!(mono_glyph(white, black, glyph, defaults), (true, glyph));
+        assert_eq!(
+            mono_glyph(black, black, glyph, defaults),
+            (false, ' ' as u32)
+        );
+        assert_eq!(mono_glyph(gray, gray, glyph, defaults), (false, '▒' as u32));
+        assert_eq!(
+            mono_glyph(white, white, glyph, defaults),
+            (false, '█' as u32)
+        );
+        // Light default backgrounds invert coverage
+        assert_eq!(
+            mono_glyph(white, white, glyph, (white, black)),
+            (false, ' ' as u32)
+        );
+    }
+
+    #[test]
+    fn forks_share_the_palette() {
+        let mut painter =
+            Painter::with_capabilities(Box::new(io::sink()), true, false, ColorDepth::Xterm256);
+        let mut fork = painter.fork();
+
+        fork.palette().set_color(1, Color::new(10, 20, 30));
+
+        assert!(Rc::ptr_eq(&painter.palette, &fork.palette));
+        assert_eq!(painter.palette().color(1), Color::new(10, 20, 30));
+    }
+}
@@ -1,6 +1,8 @@
 use crate::gfx::Color;
 use crate::utils::FourBits::{self, *};
 
+use super::split_colors;
+
 /// Turn a quadrant of four colors into two colors and a quadrant unicode character.
 pub fn binarize_quandrant(
     (x, y, z, w): (Color, Color, Color, Color),
@@ -38,3 +40,133 @@ pub fn binarize_quandrant(
         B1111 => ("▄", x.avg_with(y), z.avg_with(w)),
     }
 }
+
+/// Turn a 2x3 grid of colors in row-major order into a sextant character and two colors.
+pub fn binarize_sextant(colors: [Color; 6]) -> (char, Color, Color) {
+    let (mask, background, foreground) = split_colors(&colors);
+    let ch = match mask {
+        0 => ' ',
+        // Left and right halves are not part of the sextant block
+        0b010101 => '▌',
+        0b101010 => '▐',
+        0b111111 => '█',
+        mask => {
+            let skipped = (mask > 0b010101) as u32 + (mask > 0b101010) as u32;
+
+            char::from_u32(0x1fb00 + mask - 1 - skipped).unwrap_or(' ')
+        }
+    };
+
+    (ch, background, foreground)
+}
+
+/// Get the blocks of a sextant character, as a mask in row-major order on a 2x3 grid
+pub fn sextant_mask(ch: char) -> Option<u32> {
+    let offset = (ch as u32)
+        .checked_sub(0x1fb00)
+        .filter(|&offset| offset < 60)?;
+    // Skip the left and right halves, not part of the sextant block
+    let mask = offset + 1;
+    let mask = mask + (mask >= 0b010101) as u32;
+
+    Some(mask + (mask >= 0b101010) as u32)
+}
+
+/// Turn a 2x4 grid of colors in row-major order into an octant character and two colors.
+pub fn binarize_octant(colors: [Color; 8]) -> (char, Color, Color) {
+    let (mask, background, foreground) = split_colors(&colors);
+
+    match OCTANTS[mask as usize] {
+        // Corner octants have no glyph of their own, draw their complement with swapped colors
+        0 => (
+            octant(OCTANTS[(!mask & 0xff) as usize]),
+            foreground,
+            background,
+        ),
+        codepoint => (octant(codepoint), background, foreground),
+    }
+}
+
+fn octant(codepoint: u32) -> char {
+    char::from_u32(codepoint).unwrap_or(' ')
+}
+
+/// Get the blocks of an octant character, as a mask in row-major order on a 2x4 grid
+pub fn octant_mask(ch: char) -> Option<u32> {
+    if !('\u{1cd00}'..='\u{1cde5}').contains(&ch) {
+        return None;
+    }
+
+    OCTANTS
+        .iter()
+        .position(|&codepoint| codepoint == ch as u32)
+        .map(|mask| mask as u32)
+}
+
+/// Characters for every octant pattern, octants being numbered in row-major order.
+///
+/// Patterns already encoded outside of the octant block use existing block characters.
+const OCTANTS: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut next = 0x1cd00;
+    let mut mask = 0;
+
+    while mask < 256 {
+        table[mask] = match mask {
+            0x00 => ' ' as u32,
+            0xff => '█' as u32,
+            // Quadrants
+            0x05 => '▘' as u32,
+            0x0a => '▝' as u32,
+            0x0f => '▀' as u32,
+            0x50 => '▖' as u32,
+            0x55 => '▌' as u32,
+            0x5a => '▞' as u32,
+            0x5f => '▛' as u32,
+            0xa0 => '▗' as u32,
+            0xa5 => '▚' as u32,
+            0xaa => '▐' as u32,
+            0xaf => '▜' as u32,
+            0xf0 => '▄' as u32,
+            0xf5 => '▙' as u32,
+            0xfa => '▟' as u32,
+            // Horizontal quarters
+            0x03 => 0x1fb82,
+            0x3f => 0x1fb85,
+            0xc0 => '▂' as u32,
+            0xfc => '▆' as u32,
+            // Vertical quarters
+            0x14 => 0x1fbe6,
+            0x28 => 0x1fbe7,
+            // Corner octants
+            0x01 | 0x02 | 0x40 | 0x80 => 0,
+            _ => {
+                next += 1;
+
+                next - 1
+            }
+        };
+
+        mask += 1;
+    }
+
+    table
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_corner_octants() {
+        let (dark, bright) = (Color::new(10, 20, 30), Color::new(240, 220, 200));
+
+        for corner in [0, 1, 6, 7] {
+            let colors = std::array::from_fn(|index| if index == corner { bright } else { dark });
+            let (glyph, background, foreground) = binarize_octant(colors);
+
+            assert_ne!(glyph, ' ', "corner {corner}");
+            assert_ne!(background, foreground, "corner {corner}");
+        }
+    }
+}
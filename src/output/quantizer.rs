@@ -7,105 +7,208 @@ enum Channel {
     B,
 }
 
-const COLOR_BUCKETS: usize = 8;
-const COLORS: usize = 2_usize.pow(COLOR_BUCKETS as u32);
-
-/// Find the closest color to `color` on `palette` using a binary search
-pub fn palette_color(palette: &[Color; COLORS], color: Color) {
-    let mut size = palette.len() / 2;
-    let mut iter = palette.iter();
-    let mut prev = iter.next();
+/// Maximum number of colors in a palette
+pub const COLORS: usize = 256;
+
+/// Bits per channel used to index the nearest color cache
+const CACHE_BITS: u32 = 5;
+const CACHE_SIZE: usize = 1 << (CACHE_BITS * 3);
+
+/// Bits per channel of the histogram bins
+const HISTOGRAM_BITS: u32 = 4;
+
+/// A reduced color palette with a cache for nearest color lookups.
+pub struct Palette {
+    colors: Vec<Color>,
+    cache: Vec<u16>,
 }
 
-fn distance(a: Color, b: Color) {}
-
-pub fn quantize(pixels: &[u8]) -> [Color; COLORS] {
-    let mut min = Color::black();
-    let mut max = Color::black();
-    let mut bucket = Vec::<Color>::new();
-    let mut pixels_iter = pixels.iter();
-    let mut bucket_iter = bucket.iter_mut();
-
-    // Step 1: find the dominant channel
-    loop {
-        match (
-            bucket_iter.next(),
-            pixels_iter.next(),
-            pixels_iter.next(),
-            pixels_iter.next(),
-            pixels_iter.next(),
-        ) {
-            (Some(color), Some(r), Some(g), Some(b), Some(_)) => {
-                // Save the color in a bucket
-                color.set_r(*r);
-                color.set_g(*g);
-                color.set_b(*b);
-
-                min.set_r(min.r().min(color.r()));
-                min.set_g(min.g().min(color.g()));
-                min.set_b(min.b().min(color.b()));
-
-                max.set_r(max.r().max(color.r()));
-                max.set_g(max.g().max(color.g()));
-                max.set_b(max.b().max(color.b()));
-            }
-            _ => break,
+impl Palette {
+    pub fn new(colors: Vec<Color>) -> Self {
+        Self {
+            colors,
+            cache: vec![u16::MAX; CACHE_SIZE],
+        }
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    /// Find the index of the closest color to `color` on the palette
+    pub fn index_of(&mut self, color: Color) -> u8 {
+        let shift = 8 - CACHE_BITS;
+        let key = (color.r as usize >> shift) << (CACHE_BITS * 2)
+            | (color.g as usize >> shift) << CACHE_BITS
+            | (color.b as usize >> shift);
+
+        if self.cache[key] == u16::MAX {
+            self.cache[key] = palette_color(&self.colors, color) as u16;
+        }
+
+        self.cache[key] as u8
+    }
+}
+
+/// Count colors in bins of similar colors
+pub fn histogram(colors: &[Color]) -> Vec<u32> {
+    let shift = 8 - HISTOGRAM_BITS;
+    let mut bins = vec![0; 1 << (HISTOGRAM_BITS * 3)];
+
+    for color in colors {
+        let key = (color.r as usize >> shift) << (HISTOGRAM_BITS * 2)
+            | (color.g as usize >> shift) << HISTOGRAM_BITS
+            | (color.b as usize >> shift);
+
+        bins[key] += 1;
+    }
+
+    bins
+}
+
+/// Find the closest color to `color` on `palette` using a linear search
+pub fn palette_color(palette: &[Color], color: Color) -> usize {
+    let mut nearest = (0, u32::MAX);
+
+    for (index, candidate) in palette.iter().enumerate() {
+        let distance = distance(*candidate, color);
+
+        if distance < nearest.1 {
+            nearest = (index, distance);
         }
     }
 
+    nearest.0
+}
+
+fn distance(a: Color, b: Color) -> u32 {
+    let diff = a.cast::<i32>() - b.cast::<i32>();
+
+    (diff * diff).sum() as u32
+}
+
+/// Build a palette of at most `COLORS` colors using median-cut.
+///
+/// The `colors` slice is sorted in place while splitting buckets.
+pub fn quantize(colors: &mut [Color]) -> Palette {
+    // Each bucket is a range of `colors`, with the channel it should be split on
+    let mut buckets = vec![bucket(colors, 0, colors.len())];
+
+    // Step 1: split the bucket with the widest channel range until we have enough colors
+    while buckets.len() < COLORS {
+        let widest = buckets
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, bucket)| bucket.range);
+
+        let index = match widest {
+            Some((index, bucket)) if bucket.range > 0 => index,
+            // Every bucket only contains a single color, we're done
+            _ => break,
+        };
+
+        // Step 2: perform median-cut on the dominant channel
+        let Bucket {
+            start,
+            end,
+            channel,
+            ..
+        } = buckets[index];
+        let slice = &mut colors[start..end];
+
+        slice.sort_unstable_by_key(match channel {
+            Channel::R => |c: &Color| c.r,
+            Channel::G => |c: &Color| c.g,
+            Channel::B => |c: &Color| c.b,
+        });
+
+        let middle = start + slice.len() / 2;
+
+        buckets[index] = bucket(colors, start, middle);
+        buckets.push(bucket(colors, middle, end));
+    }
+
+    // Step 3: get the average color in each bucket
+    let palette = buckets
+        .into_iter()
+        .filter(|bucket| bucket.end > bucket.start)
+        .map(|Bucket { start, end, .. }| {
+            let sum = colors[start..end]
+                .iter()
+                .fold(Color::<u32>::splat(0), |sum, color| {
+                    sum + color.cast::<u32>()
+                });
+
+            (sum / (end - start) as u32).cast()
+        })
+        .collect();
+
+    Palette::new(palette)
+}
+
+struct Bucket {
+    start: usize,
+    end: usize,
+    channel: Channel,
+    range: u8,
+}
+
+/// Create a bucket and find its dominant channel
+fn bucket(colors: &[Color], start: usize, end: usize) -> Bucket {
+    let (min, max) = colors[start..end].iter().fold(
+        (Color::splat(u8::MAX), Color::splat(u8::MIN)),
+        |(min, max), color| {
+            (
+                Color::new(min.r.min(color.r), min.g.min(color.g), min.b.min(color.b)),
+                Color::new(max.r.max(color.r), max.g.max(color.g), max.b.max(color.b)),
+            )
+        },
+    );
     let ranges = [
-        (Channel::R, max.r() - min.r()),
-        (Channel::G, max.g() - min.g()),
-        (Channel::B, max.b() - min.b()),
+        (Channel::R, max.r.saturating_sub(min.r)),
+        (Channel::G, max.g.saturating_sub(min.g)),
+        (Channel::B, max.b.saturating_sub(min.b)),
     ];
-    let (channel, _) = ranges
-        .iter()
-        .reduce(|a, b| if a.1 > b.1 { a } else { b })
+    let (channel, range) = ranges
+        .into_iter()
+        .reduce(|a, b| if a.1 >= b.1 { a } else { b })
         .unwrap();
 
-    // Step 2: perform median-cut
-    for i in 1..=COLOR_BUCKETS {
-        let buckets = 2_usize.pow(i as u32);
-        let size = bucket.len() / buckets;
-
-        for j in 0..buckets {
-            let start = j * size;
-            let end = start + size;
-            let slice = &mut bucket[start..end];
-
-            slice.sort_unstable_by(match channel {
-                Channel::R => |a: &Color, b: &Color| a.r().cmp(&b.r()),
-                Channel::G => |a: &Color, b: &Color| a.g().cmp(&b.g()),
-                Channel::B => |a: &Color, b: &Color| a.b().cmp(&b.b()),
-            });
-        }
+    Bucket {
+        start,
+        end,
+        channel,
+        range,
     }
+}
 
-    // Step 3: get the average color in each bucket
-    let mut palette = [Color::black(); COLORS];
-    let size = bucket.len() / palette.len();
-
-    for (i, color) in palette.iter_mut().enumerate() {
-        let start = i * size;
-        let end = start + size;
-        let slice = &bucket[start..end];
-        let mut sum = None;
-
-        for color in slice.into_iter() {
-            sum = Some(match sum {
-                None => color.cast(),
-                Some(sum) => color.cast() + sum,
-            })
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if let Some(sum) = sum {
-            let avg = sum / size as u32;
+    #[test]
+    fn limits_palette_size() {
+        // Every color with 6 bits per channel
+        let mut colors = (0..1 << 18)
+            .map(|index: u32| {
+                let channel = |shift: u32| ((index >> shift) & 0x3f) as u8 * 4;
 
-            color.set_r(avg.r() as u8);
-            color.set_g(avg.g() as u8);
-            color.set_b(avg.b() as u8);
-        }
+                Color::new(channel(12), channel(6), channel(0))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(quantize(&mut colors).colors().len(), COLORS);
     }
 
-    palette
+    #[test]
+    fn keeps_few_colors() {
+        let (red, blue) = (Color::new(255, 0, 0), Color::new(0, 0, 255));
+        let mut colors = [red, blue, red, red, blue];
+        let mut palette = quantize(&mut colors);
+        let found = palette.colors().to_vec();
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[palette.index_of(red) as usize], red);
+        assert_eq!(found[palette.index_of(blue) as usize], blue);
+    }
 }
@@ -0,0 +1,142 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{gfx::Size, utils::log};
+
+use super::FrameSink;
+
+/// Number of events buffered before writing them to the file
+const FLUSH_EVENTS: usize = 64;
+
+/// Records painted frames in the asciinema v2 format.
+///
+/// The header is written once the terminal size is known, followed by
+/// a full frame, frame updates as output events and resizes as resize events.
+/// Events are buffered, and written on resizes, every few events and when dropped.
+pub struct Recording {
+    file: BufWriter<File>,
+    /// Number of events written since the last flush
+    events: usize,
+    start: Instant,
+    size: Option<Size>,
+    /// Whether the header and a full frame were written
+    started: bool,
+}
+
+impl Recording {
+    pub fn create(path: &Path) -> io::Result<Recording> {
+        Ok(Recording {
+            file: BufWriter::new(File::create(path)?),
+            events: 0,
+            start: Instant::now(),
+            size: None,
+            started: false,
+        })
+    }
+
+    fn write_header(&mut self, size: Size) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let term = std::env::var("TERM").unwrap_or_default();
+
+        writeln!(
+            self.file,
+            r#"{{"version": 2, "width": {}, "height": {}, "timestamp": {}, "env": {{"TERM": {}}}}}"#,
+            size.width,
+            size.height,
+            timestamp,
+            json_string(&term)
+        )
+    }
+
+    /// Write an event, `data` being a JSON string
+    fn write_event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+
+        writeln!(self.file, "[{time:.6}, \"{code}\", {data}]")?;
+
+        self.events += 1;
+
+        if self.events >= FLUSH_EVENTS {
+            self.flush()?
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.events = 0;
+        self.file.flush()
+    }
+}
+
+impl FrameSink for Recording {
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        if !self.started {
+            return Ok(());
+        }
+
+        self.write_event("o", &json_string(&String::from_utf8_lossy(frame)))
+    }
+
+    fn needs_full_frame(&mut self) -> bool {
+        self.size.is_some() && !self.started
+    }
+
+    fn write_full_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let Some(size) = self.size else {
+            return Ok(());
+        };
+
+        self.write_header(size)?;
+        self.started = true;
+        self.write_frame(frame)
+    }
+
+    fn resize(&mut self, size: Size) {
+        if self.size == Some(size) {
+            return;
+        }
+
+        self.size = Some(size);
+
+        if self.started {
+            let size = json_string(&format!("{}x{}", size.width, size.height));
+
+            if let Err(error) = self.write_event("r", &size).and_then(|_| self.flush()) {
+                log::debug!("recording: {}", error);
+            }
+        }
+    }
+}
+
+/// Encode a string as a JSON string literal
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+
+    json.push('"');
+
+    for char in value.chars() {
+        match char {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            '\0'..='\x1f' | '\x7f' => {
+                write!(json, "\\u{:04x}", char as u32).unwrap();
+            }
+            char => json.push(char),
+        }
+    }
+
+    json.push('"');
+
+    json
+}
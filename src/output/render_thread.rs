@@ -1,12 +1,22 @@
 use std::{
+    path::PathBuf,
     sync::mpsc::{self, Receiver, Sender},
     thread::{self, JoinHandle},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use crate::cli::CommandLine;
+use crate::{
+    cli::CommandLine,
+    gfx::{Rect, Size},
+    utils::log,
+};
+
+use super::{Capture, FrameSync, Recording, Renderer};
 
-use super::{FrameSync, Renderer};
+/// Time without updates after which a page is considered loaded
+const SCREENSHOT_IDLE: Duration = Duration::from_secs(2);
+/// Maximum time spent waiting for a page to stop updating
+const SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(15);
 
 /// Control a rendering thread that lazily starts.
 /// This allows the `Bridge` struct to be used in places
@@ -17,11 +27,36 @@ pub struct RenderThread {
 }
 
 type RenderClosure = Box<dyn FnMut(&mut Renderer) + Send>;
+type ExitClosure = Box<dyn FnOnce(i32) + Send>;
 enum Message {
     Run(RenderClosure),
+    OnExit(ExitClosure),
+    DrawBitmap(Bitmap),
     Shutdown,
 }
 
+/// Pixels of the page to draw, `ack` releases them once they're not needed anymore.
+pub struct Bitmap {
+    pub pixels: &'static [u8],
+    pub size: Size,
+    pub rect: Rect,
+    pub ack: Box<dyn FnOnce() + Send>,
+}
+
+impl Bitmap {
+    /// Copy the pixels to the renderer, then release them
+    fn copy_to(self, renderer: &mut Renderer) {
+        renderer.receive_bitmap(self.pixels, self.size, self.rect);
+        (self.ack)()
+    }
+}
+
+impl Default for RenderThread {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RenderThread {
     pub fn new() -> Self {
         Self {
@@ -55,17 +90,64 @@ impl RenderThread {
         self.send(Message::Run(Box::new(run)))
     }
 
+    /// Set the closure called with an exit code when the rendering thread
+    /// wants the browser to quit, such as once a screenshot is saved.
+    pub fn on_exit<F>(&mut self, run: F)
+    where
+        F: FnOnce(i32) + Send + 'static,
+    {
+        self.send(Message::OnExit(Box::new(run)))
+    }
+
+    /// Draw a bitmap on the rendering thread.
+    /// Bitmaps are copied and released when received, then drawn together on the next frame.
+    pub fn draw_bitmap(&mut self, bitmap: Bitmap) {
+        self.send(Message::DrawBitmap(bitmap))
+    }
+
     /// Boot the rendering thread, contains a simple event loop.
     fn boot(rx: Receiver<Message>) {
         let cmd = CommandLine::parse();
-        let mut sync = FrameSync::new(cmd.fps);
+        let mut sync = FrameSync::new(cmd.min_fps, cmd.fps);
         let mut renderer = Renderer::new();
+
+        renderer.set_glyphs(cmd.glyphs);
+        renderer.set_backend(cmd.renderer);
+        renderer.set_dither(cmd.dither);
+        renderer.set_split_frames(!cmd.no_split_frames);
+        renderer.set_capture(Capture::new(cmd.capture, cmd.capture_frames));
+
+        if let Some(depth) = cmd.color {
+            renderer.set_color_depth(depth)
+        }
+
+        if let Some(protocol) = cmd.graphics {
+            renderer.set_graphics(protocol)
+        }
+
+        if let Some(ref mirror) = cmd.mirror {
+            match mirror.open() {
+                Ok(sink) => renderer.add_mirror(sink),
+                Err(error) => log::warning!("mirror: failed to open {:?}: {}", mirror, error),
+            }
+        }
+
+        if let Some(ref path) = cmd.record {
+            match Recording::create(path) {
+                Ok(recording) => renderer.add_mirror(Box::new(recording)),
+                Err(error) => log::warning!("recording: failed to create {:?}: {}", path, error),
+            }
+        }
+
         let mut needs_render = false;
+        let mut screenshot = cmd.screenshot.map(Screenshot::new);
+        let mut on_exit: Option<ExitClosure> = None;
 
         loop {
             // Get a deadline for the next frame
             let deadline = sync.deadline();
-            let mut wait = true;
+            // Don't block if a skipped frame still needs to be rendered
+            let mut wait = !needs_render;
 
             loop {
                 let message = if wait {
@@ -73,7 +155,13 @@ impl RenderThread {
                     // until we get a message, after which we schedule a render.
                     wait = false;
 
-                    rx.recv().ok()
+                    match screenshot.as_ref().and_then(Screenshot::deadline) {
+                        // Wake up to save a pending screenshot
+                        Some(time) => rx
+                            .recv_timeout(time.saturating_duration_since(Instant::now()))
+                            .ok(),
+                        None => rx.recv().ok(),
+                    }
                 } else {
                     // On subsequence iterations, we want to process a maximum
                     // number of events until the deadline for the next frame.
@@ -85,22 +173,71 @@ impl RenderThread {
                     None => break,
                     // Shutdown the thread
                     Some(Message::Shutdown) => return,
+                    // Keep the closure to call when exiting
+                    Some(Message::OnExit(closure)) => on_exit = Some(closure),
                     // Run a closure and schedule a render
                     Some(Message::Run(mut closure)) => {
                         closure(&mut renderer);
 
+                        if let Some(ref mut screenshot) = screenshot {
+                            screenshot.update(false)
+                        }
+
+                        needs_render = true;
+                    }
+                    // Copy the bitmap right away so Chromium can reuse it,
+                    // even if the next frames are skipped
+                    Some(Message::DrawBitmap(bitmap)) => {
+                        bitmap.copy_to(&mut renderer);
+
+                        if let Some(ref mut screenshot) = screenshot {
+                            screenshot.update(true)
+                        }
+
                         needs_render = true;
                     }
                 }
             }
 
-            // Render if needed
+            // Skip the frame if the terminal is still busy with previous ones,
+            // the next render will include every change made in the meantime
             if needs_render {
-                needs_render = false;
+                let pending = renderer.pending_output();
 
+                if sync.is_behind(pending) {
+                    log::debug!(
+                        "render: skipping frame, {} bytes pending at {:.1} FPS",
+                        pending,
+                        sync.fps()
+                    );
+                    sync.skip();
+
+                    continue;
+                }
+            }
+
+            // Render if needed
+            if needs_render {
                 // Update the frame sync timings
                 sync.start();
-                renderer.render().unwrap();
+
+                renderer.draw_damage();
+
+                let stats = renderer.render().unwrap();
+
+                sync.end(stats);
+
+                // Regions still processed by the GPU are painted on the next frame
+                needs_render = renderer.has_pending_frames();
+            }
+
+            if !needs_render && screenshot.as_ref().is_some_and(Screenshot::is_due) {
+                let code = screenshot.take().unwrap().save(&renderer);
+
+                match on_exit.take() {
+                    Some(exit) => exit(code),
+                    None => log::warning!("screenshot: no exit handler, keep running"),
+                }
             }
         }
     }
@@ -119,3 +256,93 @@ impl RenderThread {
         }
     }
 }
+
+/// A screenshot saved once the page is loaded, after which the browser exits.
+struct Screenshot {
+    path: PathBuf,
+    /// Time at which the page was first drawn
+    drawn: Option<Instant>,
+    updated: Instant,
+}
+
+impl Screenshot {
+    fn new(path: PathBuf) -> Screenshot {
+        Screenshot {
+            path,
+            drawn: None,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Record an update to the page, `bitmap` being whether it was drawn
+    fn update(&mut self, bitmap: bool) {
+        self.updated = Instant::now();
+
+        if bitmap {
+            self.drawn.get_or_insert(self.updated);
+        }
+    }
+
+    /// Time at which the screenshot should be saved, once the page was drawn
+    fn deadline(&self) -> Option<Instant> {
+        let drawn = self.drawn?;
+
+        Some((self.updated + SCREENSHOT_IDLE).min(drawn + SCREENSHOT_TIMEOUT))
+    }
+
+    fn is_due(&self) -> bool {
+        self.deadline().is_some_and(|time| time <= Instant::now())
+    }
+
+    /// Save the cells on screen, returning the code to exit with
+    fn save(&self, renderer: &Renderer) -> i32 {
+        match renderer.save_screenshot(&self.path) {
+            Ok(()) => 0,
+            Err(error) => {
+                log::error!("screenshot: failed to save {:?}: {}", self.path, error);
+
+                1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn releases_bitmaps_before_rendering() {
+        let mut renderer = Renderer::with_output(Box::new(io::sink()));
+        let size = Size::new(10, 5);
+        let pixels: &'static [u8] = vec![255; 10 * 5 * 32 * 4].leak();
+        let acks = Arc::new(AtomicUsize::new(0));
+
+        renderer.set_size(size);
+
+        // Bitmaps received while frames are skipped
+        for _ in 0..2 {
+            let acks = acks.clone();
+
+            Bitmap {
+                pixels,
+                size: size * (4, 8),
+                rect: Rect::new(0, 0, 40, 40),
+                ack: Box::new(move || {
+                    acks.fetch_add(1, Ordering::Relaxed);
+                }),
+            }
+            .copy_to(&mut renderer);
+        }
+
+        assert_eq!(acks.load(Ordering::Relaxed), 2);
+    }
+}
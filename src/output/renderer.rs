@@ -1,65 +1,277 @@
-use std::{
-    io::{self, Write},
-    ptr,
-    rc::Rc,
-    time::Instant,
-};
+#[cfg(test)]
+mod tests;
+
+use std::{io, path::Path, rc::Rc, time::Instant};
 
+use chrono::Local;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 use crate::{
     gfx::{Color, Point, Rect, Size},
-    input::Key,
+    input::{Key, TerminalColor},
     ui::navigation::{Navigation, NavigationAction},
     utils::log,
 };
 
-use super::{binarize_quandrant, Cell, Grapheme, Painter};
-use super::fidelitty::*;
-
-struct FidelittyState {
-    ctx: FttyContext,
-    pipeline: FttyPipeline,
-}
+use super::{
+    copy_pixels, save_screenshot, Backend, Capture, Cell, ColorDepth, Damage, Dither, FrameSink,
+    FrameStats, GlyphSet, Grapheme, Graphics, GraphicsProtocol, Painter, RenderBackend,
+};
 
 pub struct Renderer {
     nav: Navigation,
     cells: Vec<(Cell, Cell)>,
     painter: Painter,
     size: Size,
-    ftty: Option<FidelittyState>,
+    backend: Box<dyn RenderBackend>,
+    /// Page pixels encoded in BGRA8888, drawn again when switching backends
+    /// and read by graphics protocols
+    frame: Vec<u8>,
+    /// Region of the frame received but not drawn by the backend yet
+    damage: Option<Damage>,
+    capture: Capture,
+    graphics: Option<Graphics>,
+    glyphs: GlyphSet,
+    dither: Dither,
+    /// Whether the color depth was set from the command line
+    color_forced: bool,
+    /// Whether changed cells were left for the next frame to stay under the painter budget
+    deferred: bool,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Renderer {
     pub fn new() -> Renderer {
-        let ftty_ctx = unsafe { ftty_context_create(1) };
-        let ftty = if ftty_ctx.is_null() {
-            log::debug!("fidelitty: Vulkan not available, using fallback renderer");
-            None
-        } else {
-            log::debug!("fidelitty: Vulkan context created");
-            Some(FidelittyState {
-                ctx: ftty_ctx,
-                pipeline: ptr::null_mut(),
-            })
-        };
+        Self::with_painter(Painter::new())
+    }
+
+    /// Create a renderer painting frames to a custom output
+    pub fn with_output(output: Box<dyn FrameSink>) -> Renderer {
+        Self::with_painter(Painter::with_output(output))
+    }
 
+    fn with_painter(painter: Painter) -> Renderer {
         Renderer {
             nav: Navigation::new(),
             cells: Vec::with_capacity(0),
-            painter: Painter::new(),
+            painter,
             size: Size::new(0, 0),
-            ftty,
+            backend: Backend::Cpu.create(GlyphSet::Quadrant).unwrap(),
+            frame: Vec::new(),
+            damage: None,
+            capture: Capture::default(),
+            graphics: None,
+            glyphs: GlyphSet::Quadrant,
+            dither: Dither::None,
+            color_forced: false,
+            deferred: false,
         }
     }
 
     pub fn enable_true_color(&mut self) {
-        self.painter.set_true_color(true)
+        if !self.color_forced {
+            self.painter.set_color_depth(ColorDepth::TrueColor)
+        }
+    }
+
+    /// Wrap frames in synchronized updates once the terminal confirmed its support
+    pub fn enable_synchronized_output(&mut self) {
+        log::debug!("painter: enabling synchronized output");
+
+        self.painter.set_synchronized(true)
+    }
+
+    /// Split frames too large for terminals without synchronized updates to parse at once,
+    /// trading tearing within a frame for rows painted by the next frames.
+    ///
+    /// Enabled by default, frames are split until the terminal reports supporting
+    /// synchronized updates, which never happens on terminals without support.
+    pub fn set_split_frames(&mut self, split_frames: bool) {
+        self.painter.set_split_frames(split_frames)
+    }
+
+    /// Override the detected color depth
+    pub fn set_color_depth(&mut self, depth: ColorDepth) {
+        self.color_forced = true;
+        self.painter.set_color_depth(depth)
+    }
+
+    /// Set the glyphs used by the CPU renderer
+    pub fn set_glyphs(&mut self, glyphs: GlyphSet) {
+        self.glyphs = glyphs;
+
+        if self.backend.kind() == Backend::Cpu {
+            self.use_backend(Backend::Cpu).ok();
+        }
+    }
+
+    /// Set the backend converting pixels to cells, or pick the best available one
+    pub fn set_backend(&mut self, backend: Option<Backend>) {
+        if let Err(error) = self.use_backend(backend.unwrap_or(Backend::Fidelitty)) {
+            // Only tell why a backend the user asked for is not used
+            match backend {
+                Some(kind) => log::warning!("renderer: {:?} backend unavailable: {}", kind, error),
+                None => log::debug!("renderer: {}", error),
+            }
+
+            self.use_backend(Backend::Cpu).ok();
+        }
+    }
+
+    /// Switch to the next available backend
+    pub fn cycle_backend(&mut self) {
+        let current = self.backend.kind();
+        let mut backend = current.next();
+
+        while backend != current {
+            match self.use_backend(backend) {
+                Ok(()) => break,
+                Err(error) => log::debug!("renderer: {}", error),
+            }
+
+            backend = backend.next()
+        }
+    }
+
+    /// Replace the backend and draw the page again using it
+    fn use_backend(&mut self, kind: Backend) -> Result<(), String> {
+        let mut backend = kind.create(self.glyphs)?;
+        let size = self.size.cast::<usize>();
+
+        if size.width > 0 && size.height > 0 {
+            if !backend.set_size(size) {
+                return Err(format!(
+                    "{:?} backend cannot draw {}x{} cells",
+                    kind, size.width, size.height
+                ));
+            }
+
+            backend.draw(
+                &self.frame,
+                size * (4, 8),
+                Damage {
+                    top: 0,
+                    left: 0,
+                    right: size.width,
+                    bottom: size.height,
+                },
+                &mut self.cells[size.width..],
+            );
+        }
+
+        log::debug!("renderer: using {:?} backend", kind);
+
+        self.backend = backend;
+
+        Ok(())
+    }
+
+    /// Use a color reported by the terminal to reduce colors
+    pub fn set_terminal_color(&mut self, kind: TerminalColor, color: Color) {
+        let mut palette = self.painter.palette();
+
+        match kind {
+            TerminalColor::Palette(code) => palette.set_color(code, color),
+            TerminalColor::Foreground => palette.set_foreground(color),
+            TerminalColor::Background => palette.set_background(color),
+        }
+    }
+
+    /// Send a copy of the painted frames to another sink
+    pub fn add_mirror(&mut self, mirror: Box<dyn FrameSink>) {
+        self.painter.add_mirror(mirror)
+    }
+
+    /// Save the cells on screen as an image if the path ends with `.png`, or as text otherwise
+    pub fn save_screenshot(&self, path: &Path) -> io::Result<()> {
+        let cells = self
+            .cells
+            .iter()
+            .map(|(previous, _)| previous)
+            .collect::<Vec<_>>();
+
+        save_screenshot(path, &cells, self.size.width as usize)
+    }
+
+    /// Set where and which page bitmaps are captured
+    pub fn set_capture(&mut self, capture: Capture) {
+        self.capture = capture
+    }
+
+    /// Set the dithering used with the xterm 256 colors palette
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.dither = dither
+    }
+
+    /// Draw the page background as pixels using a terminal graphics protocol
+    pub fn set_graphics(&mut self, protocol: GraphicsProtocol) {
+        let mut graphics = Graphics::new(protocol);
+
+        graphics.set_size(self.size.cast());
+
+        self.graphics = Some(graphics)
+    }
+
+    /// Start using a graphics protocol once the terminal confirmed its support
+    pub fn enable_graphics(&mut self, protocol: GraphicsProtocol) {
+        if let Some(ref mut graphics) = self.graphics {
+            if graphics.protocol() == protocol && !graphics.is_enabled() {
+                log::debug!("graphics: enabling {:?}", protocol);
+
+                graphics.enable()
+            }
+        }
+    }
+
+    /// Detect graphics protocol support from the terminal name
+    pub fn set_terminal_name(&mut self, name: &str) {
+        if let Some(protocol) = self.graphics.as_ref().map(|graphics| graphics.protocol()) {
+            if protocol.supported_by(name) {
+                self.enable_graphics(protocol)
+            }
+        }
+    }
+
+    /// Set the size of a terminal cell in device pixels
+    pub fn set_cell_size(&mut self, size: Size) {
+        if let Some(ref mut graphics) = self.graphics {
+            graphics.set_cell_size(size.cast())
+        }
     }
 
     pub fn keypress(&mut self, key: &Key) -> io::Result<NavigationAction> {
-        let action = self.nav.keypress(key);
+        let action = match self.nav.keypress(key) {
+            NavigationAction::CycleBackend() => {
+                self.cycle_backend();
+
+                NavigationAction::Ignore
+            }
+            // Shift+Alt+Up captures the page pixels
+            NavigationAction::Capture() => {
+                self.capture
+                    .save(&self.frame, self.size.cast::<usize>() * (4, 8));
+
+                NavigationAction::Ignore
+            }
+            // Alt+Up saves a screenshot in the working directory
+            NavigationAction::Screenshot() => {
+                let path = format!("carbonyl-{}.png", Local::now().format("%Y%m%d-%H%M%S"));
+
+                match self.save_screenshot(Path::new(&path)) {
+                    Ok(()) => log::debug!("saved screenshot to {}", path),
+                    Err(error) => log::warning!("failed to save screenshot to {}: {}", path, error),
+                }
+
+                NavigationAction::Ignore
+            }
+            action => action,
+        };
 
         Ok(action)
     }
@@ -89,6 +301,9 @@ impl Renderer {
 
     pub fn set_size(&mut self, size: Size) {
         self.nav.set_size(size);
+        // The navigation bar takes the first row of the terminal
+        self.painter
+            .set_size(Size::new(size.width, size.height + 1));
         self.size = size;
 
         let mut x = 0;
@@ -110,36 +325,38 @@ impl Renderer {
             cell
         });
 
-        // Create or resize fidelitty pipeline
-        if let Some(ref mut ftty) = self.ftty {
-            let w = size.width as u16;
-            let h = size.height as u16;
-
-            unsafe {
-                if ftty.pipeline.is_null() {
-                    ftty.pipeline = ftty_context_create_render_pipeline_ex(
-                        ftty.ctx, w, h,
-                        FttyPixelFormat::Bgra, 4, 8,
-                    );
-                    if ftty.pipeline.is_null() {
-                        log::debug!("fidelitty: failed to create render pipeline");
-                    }
-                } else {
-                    let ret = ftty_context_resize_render_pipeline(
-                        ftty.ctx, ftty.pipeline, w, h,
-                    );
-                    if ret != 0 {
-                        log::debug!("fidelitty: failed to resize render pipeline");
-                    }
-                }
-            }
+        if let Some(ref mut graphics) = self.graphics {
+            graphics.set_size(size.cast())
         }
+
+        self.frame.clear();
+        self.frame
+            .resize((size.width * size.height) as usize * 32 * 4, 0);
+        self.damage = None;
+
+        if !self.backend.set_size(size.cast()) {
+            log::debug!("renderer: {:?} backend unavailable", self.backend.kind());
+
+            self.use_backend(Backend::Cpu).ok();
+        }
+    }
+
+    /// Whether regions drawn asynchronously or deferred cells still need to be painted
+    pub fn has_pending_frames(&self) -> bool {
+        self.backend.has_pending() || self.deferred
+    }
+
+    /// Number of bytes written to the terminal but not transmitted yet
+    pub fn pending_output(&self) -> usize {
+        self.painter.pending_output()
     }
 
-    pub fn render(&mut self) -> io::Result<()> {
+    pub fn render(&mut self) -> io::Result<FrameStats> {
         let t_start = Instant::now();
         let size = self.size;
 
+        self.backend.collect(&mut self.cells[size.width as usize..]);
+
         for (origin, element) in self.nav.render(size) {
             self.fill_rect(
                 Rect::new(origin.x, origin.y, element.text.width() as u32, 1),
@@ -153,46 +370,175 @@ impl Renderer {
             );
         }
 
+        if self.painter.color_depth() == ColorDepth::Xterm256 && self.dither != Dither::None {
+            self.dither_cells();
+        }
+
         let t_nav = t_start.elapsed();
 
         self.painter.begin()?;
 
-        let mut cells_painted = 0u32;
-        for (previous, current) in self.cells.iter_mut() {
-            if current == previous {
-                continue;
-            }
+        let cells_painted = match self.graphics {
+            Some(ref graphics) if graphics.is_enabled() => self.paint_graphics()?,
+            _ => self.paint_cells()?,
+        };
 
-            previous.quadrant = current.quadrant;
-            previous.background = current.background;
-            previous.foreground = current.foreground;
-            previous.codepoint = current.codepoint;
-            previous.grapheme = current.grapheme.clone();
+        let t_diff_paint = t_start.elapsed();
 
-            self.painter.paint(current)?;
-            cells_painted += 1;
-        }
+        let bytes = self.painter.end(self.nav.cursor())?;
 
-        let t_diff_paint = t_start.elapsed();
+        // Mirrors joining the session start from the cells on screen
+        if self.painter.needs_full_frame() {
+            let cells = self.cells.iter().map(|(previous, _)| previous);
 
-        self.painter.end(self.nav.cursor())?;
+            self.painter.paint_full_frame(cells, self.nav.cursor())?;
+        }
 
         let t_flush = t_start.elapsed();
 
         log::debug!(
-            "render: {} cells painted | nav: {:?} | diff+paint: {:?} | flush: {:?} | total: {:?}",
+            "render: {} cells painted in {} bytes | nav: {:?} | diff+paint: {:?} | flush: {:?} | total: {:?}",
             cells_painted,
+            bytes,
             t_nav,
             t_diff_paint - t_nav,
             t_flush - t_diff_paint,
             t_flush
         );
 
-        Ok(())
+        Ok(FrameStats {
+            bytes,
+            flush: t_flush - t_diff_paint,
+        })
+    }
+
+    /// Map the colors of page cells to the xterm palette using dithering.
+    ///
+    /// Only the rows between the first and the last changed ones are dithered again,
+    /// cells of these rows getting different indices being painted again.
+    fn dither_cells(&mut self) {
+        let width = self.size.width as usize;
+        let page = &mut self.cells[width..];
+        let changed =
+            |row: &[(Cell, Cell)]| row.iter().any(|(previous, current)| previous != current);
+        let (top, bottom) = match page.chunks(width.max(1)).position(changed) {
+            None => return,
+            Some(top) => (
+                top,
+                page.chunks(width).rposition(changed).unwrap_or(top) + 1,
+            ),
+        };
+        let page = &mut page[top * width..bottom * width];
+        let mut palette = self.painter.palette();
+        let mut colors = |color: fn(&Cell) -> Color| {
+            let colors = page.iter().map(|(_, cell)| color(cell)).collect::<Vec<_>>();
+
+            self.dither.apply(&colors, width, &mut palette)
+        };
+        let codes = colors(|cell| cell.background)
+            .into_iter()
+            .zip(colors(|cell| cell.foreground));
+
+        for ((_, cell), codes) in page.iter_mut().zip(codes) {
+            cell.palette = Some(codes)
+        }
+    }
+
+    /// Paint every cell that changed since the last render
+    fn paint_cells(&mut self) -> io::Result<u32> {
+        let width = self.size.width as usize;
+        let mut cells_painted = 0;
+
+        self.deferred = false;
+
+        for (index, (previous, current)) in self.cells.iter_mut().enumerate() {
+            // Paint whole rows in order, leaving the next ones for later frames
+            if index % width == 0 && !self.painter.has_budget() {
+                self.deferred = true;
+
+                break;
+            }
+
+            if current == previous {
+                continue;
+            }
+
+            copy_cell(previous, current);
+
+            self.painter.paint(current)?;
+            cells_painted += 1;
+        }
+
+        Ok(cells_painted)
+    }
+
+    /// Paint the damaged page region as an image, and text cells on top of it
+    fn paint_graphics(&mut self) -> io::Result<u32> {
+        let graphics = self.graphics.as_mut().unwrap();
+        let erase = graphics.protocol().draws_below_text();
+        let width = self.size.width as usize;
+
+        // Text that disappeared leaves a hole in images replacing cells
+        if !erase {
+            for (index, (previous, current)) in self.cells.iter().enumerate().skip(width) {
+                if previous.grapheme.is_some() && current.grapheme.is_none() {
+                    let (x, y) = (index % width, index / width - 1);
+
+                    graphics.damage(Damage {
+                        top: y,
+                        left: x,
+                        right: x + 1,
+                        bottom: y + 1,
+                    })
+                }
+            }
+        }
+
+        let overwritten = match graphics.take_damage() {
+            None => None,
+            Some(damage) => graphics.paint(damage, &self.frame, &mut self.painter)?,
+        };
+
+        let mut cells_painted = 0;
+
+        for (index, (previous, current)) in self.cells.iter_mut().enumerate() {
+            let (x, y) = (index % width, index / width);
+            // The navigation bar is always drawn using cells
+            let nav = y == 0;
+            let damaged = !nav && overwritten.is_some_and(|damage| damage.contains(x, y - 1));
+            let changed = current != previous;
+            let removed = previous.grapheme.is_some() && current.grapheme.is_none();
+
+            if changed {
+                copy_cell(previous, current);
+            }
+
+            if nav && changed || current.grapheme.is_some() && (changed || damaged) {
+                self.painter.paint(current)?;
+                cells_painted += 1;
+            } else if !nav && erase && removed {
+                // Erase text left on top of images
+                let mut blank = Cell::new(current.cursor.x, current.cursor.y);
+
+                blank.background = current.background;
+                self.painter.paint(&blank)?;
+                cells_painted += 1;
+            }
+        }
+
+        Ok(cells_painted)
     }
 
     /// Draw the background from a pixel array encoded in BGRA8888
     pub fn draw_background(&mut self, pixels: &[u8], pixels_size: Size, rect: Rect) {
+        self.receive_bitmap(pixels, pixels_size, rect);
+        self.draw_damage();
+    }
+
+    /// Copy the damaged region of a bitmap into the frame,
+    /// after which the pixels can be released. Regions received
+    /// before the next call to `draw_damage` are drawn together.
+    pub fn receive_bitmap(&mut self, pixels: &[u8], pixels_size: Size, rect: Rect) {
         let viewport = self.size.cast::<usize>();
 
         if pixels.len() < viewport.width * viewport.height * 32 * 4 {
@@ -204,6 +550,8 @@ impl Renderer {
             return;
         }
 
+        self.capture.receive(pixels, pixels_size.cast());
+
         let origin = rect.origin.cast::<f32>().max(0.0) / (4.0, 8.0);
         let size = rect.size.cast::<f32>().max(0.0) / (4.0, 8.0);
         let top = (origin.y.floor() as usize).min(viewport.height);
@@ -215,179 +563,48 @@ impl Renderer {
             .min(viewport.height)
             .max(top);
 
-        let use_fidelitty = match self.ftty {
-            Some(ref ftty) => !ftty.pipeline.is_null(),
-            None => false,
+        let damage = Damage {
+            top,
+            left,
+            right,
+            bottom,
         };
 
-        if use_fidelitty {
-            self.draw_background_fidelitty(pixels, pixels_size, top, left, right, bottom);
-        } else {
-            self.draw_background_fallback(pixels, pixels_size, top, left, right, bottom);
-        }
-    }
-
-    fn draw_background_fidelitty(
-        &mut self,
-        pixels: &[u8],
-        pixels_size: Size,
-        top: usize,
-        left: usize,
-        right: usize,
-        bottom: usize,
-    ) {
-        let t_start = Instant::now();
-
-        let ftty = self.ftty.as_ref().unwrap();
-        let viewport = self.size.cast::<usize>();
-        let row_length = pixels_size.width as usize;
-        let input_width = viewport.width * 4; // 4 pixels per cell, BGRA
-
-        let input_surface = unsafe { ftty_pipeline_get_input_surface(ftty.pipeline) };
-        if input_surface.is_null() {
-            return;
-        }
-
-        let dispatch_w = (right - left) as u16;
-        let dispatch_h = (bottom - top) as u16;
-
-        if dispatch_w == 0 || dispatch_h == 0 {
-            return;
-        }
-
-        // Copy BGRA pixels directly to fidelitty input surface.
-        // The GPU shader handles BGRA→RGB swizzle and 8→4 vertical downscale.
-        let bpp = 4usize; // BGRA bytes per pixel
-        let row_bytes = input_width * bpp;
-
-        if row_length == input_width {
-            // Strides match — single memcpy for the entire row block
-            let start = top * 8 * row_bytes;
-            let total = (bottom - top) * 8 * row_bytes;
-            unsafe {
-                ptr::copy_nonoverlapping(
-                    pixels.as_ptr().add(start),
-                    input_surface.add(start),
-                    total,
-                );
-            }
-        } else {
-            // Different strides — copy row by row
-            let copy_bytes = (right - left) * 4 * bpp;
-            for py in (top * 8)..(bottom * 8) {
-                let src_offset = (py * row_length + left * 4) * bpp;
-                let dst_offset = (py * input_width + left * 4) * bpp;
-                unsafe {
-                    ptr::copy_nonoverlapping(
-                        pixels.as_ptr().add(src_offset),
-                        input_surface.add(dst_offset),
-                        copy_bytes,
-                    );
-                }
-            }
-        }
-
-        let t_copy = t_start.elapsed();
-
-        unsafe {
-            let ret = ftty_context_execute_render_pipeline_region(
-                ftty.ctx,
-                ftty.pipeline,
-                left as u16,
-                top as u16,
-                dispatch_w,
-                dispatch_h,
-            );
-            if ret != 0 {
-                log::debug!("fidelitty: execute failed");
-                return;
-            }
-
-            let ret = ftty_context_wait_render_pipeline(ftty.ctx, ftty.pipeline);
-            if ret != 0 {
-                log::debug!("fidelitty: wait failed");
-                return;
-            }
-        }
-
-        let t_gpu = t_start.elapsed();
+        let copied = copy_pixels(
+            &mut self.frame,
+            viewport,
+            pixels,
+            pixels_size.cast(),
+            damage,
+        );
 
-        // Read fidelitty output into cells
-        let output_surface = unsafe { ftty_pipeline_get_output_surface(ftty.pipeline) };
-        if output_surface.is_null() {
+        if !copied || damage.is_empty() {
             return;
         }
 
-        for y in top..bottom {
-            let cell_index = (y + 1) * viewport.width;
-            let out_row = y * viewport.width;
-
-            for cx in left..right {
-                let ftty_pixel = unsafe { *output_surface.add(out_row + cx) };
-                let cell = &mut self.cells[cell_index + cx].1;
-
-                cell.background = Color::new(ftty_pixel.br, ftty_pixel.bg, ftty_pixel.bb);
-                cell.foreground = Color::new(ftty_pixel.fr, ftty_pixel.fg, ftty_pixel.fb);
-                cell.codepoint = ftty_pixel.codepoint;
-            }
+        if let Some(ref mut graphics) = self.graphics {
+            graphics.damage(damage)
         }
 
-        let t_readback = t_start.elapsed();
-
-        log::debug!(
-            "fidelitty: {}x{} region | copy: {:?} | gpu: {:?} | readback: {:?} | total: {:?}",
-            dispatch_w,
-            dispatch_h,
-            t_copy,
-            t_gpu - t_copy,
-            t_readback - t_gpu,
-            t_readback
-        );
+        self.damage = Some(match self.damage {
+            None => damage,
+            Some(previous) => previous.union(damage),
+        });
     }
 
-    fn draw_background_fallback(
-        &mut self,
-        pixels: &[u8],
-        pixels_size: Size,
-        top: usize,
-        left: usize,
-        right: usize,
-        bottom: usize,
-    ) {
-        let viewport = self.size.cast::<usize>();
-        let row_length = pixels_size.width as usize;
-        let pixel = |x: usize, y: usize| {
-            Color::new(
-                pixels[(x + y * row_length) * 4 + 2],
-                pixels[(x + y * row_length) * 4 + 1],
-                pixels[(x + y * row_length) * 4 + 0],
-            )
+    /// Draw the regions of the frame received since the last call
+    pub fn draw_damage(&mut self) {
+        let Some(damage) = self.damage.take() else {
+            return;
         };
-        let pair = |x: usize, y: usize| pixel(x, y).avg_with(pixel(x, y + 1));
+        let viewport = self.size.cast::<usize>();
 
-        for y in top..bottom {
-            let index = (y + 1) * viewport.width;
-            let start = index + left;
-            let end = index + right;
-            let (mut x, y) = (left * 4, y * 8);
-
-            for (_, cell) in &mut self.cells[start..end] {
-                let quadrant = (
-                    pair(x + 0, y + 0),
-                    pair(x + 2, y + 0),
-                    pair(x + 2, y + 4),
-                    pair(x + 0, y + 4),
-                );
-
-                cell.quadrant = quadrant;
-                let (ch, bg, fg) = binarize_quandrant(quadrant);
-                cell.background = bg;
-                cell.foreground = fg;
-                cell.codepoint = ch.chars().next().unwrap_or(' ') as u32;
-
-                x += 4;
-            }
-        }
+        self.backend.draw(
+            &self.frame,
+            viewport * (4, 8),
+            damage,
+            &mut self.cells[viewport.width..],
+        );
     }
 
     pub fn clear_text(&mut self) {
@@ -396,14 +613,8 @@ impl Renderer {
         }
     }
 
-    pub fn set_title(&self, title: &str) -> io::Result<()> {
-        let mut stdout = io::stdout();
-
-        write!(stdout, "\x1b]0;{title}\x07")?;
-        write!(stdout, "\x1b]1;{title}\x07")?;
-        write!(stdout, "\x1b]2;{title}\x07")?;
-
-        stdout.flush()
+    pub fn set_title(&mut self, title: &str) -> io::Result<()> {
+        self.painter.set_title(title)
     }
 
     pub fn fill_rect(&mut self, rect: Rect, color: Color) {
@@ -501,15 +712,11 @@ impl Renderer {
     }
 }
 
-impl Drop for Renderer {
-    fn drop(&mut self) {
-        if let Some(ref mut ftty) = self.ftty {
-            unsafe {
-                if !ftty.pipeline.is_null() {
-                    ftty_context_destroy_render_pipeline(ftty.ctx, ftty.pipeline);
-                }
-                ftty_context_destroy(ftty.ctx);
-            }
-        }
-    }
+fn copy_cell(previous: &mut Cell, current: &Cell) {
+    previous.quadrant = current.quadrant;
+    previous.background = current.background;
+    previous.foreground = current.foreground;
+    previous.codepoint = current.codepoint;
+    previous.grapheme = current.grapheme.clone();
+    previous.palette = current.palette;
 }
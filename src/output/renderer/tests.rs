@@ -0,0 +1,533 @@
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process,
+};
+
+use crate::gfx::{Color, Point, Rect, Size};
+
+use super::super::{
+    save_screenshot,
+    vt::{VirtualTerminal, VtColor},
+    Capture, CaptureFrames, ColorDepth, Dither, Mirror, Recording, Renderer,
+};
+
+const RED: Color = Color::new(255, 0, 0);
+const BLUE: Color = Color::new(0, 0, 255);
+const WHITE: Color = Color::new(255, 255, 255);
+
+/// Create a renderer for a page of `size` cells, painting to a virtual terminal
+fn renderer(size: Size) -> (Renderer, VirtualTerminal) {
+    // The navigation bar takes the first row
+    let terminal = VirtualTerminal::new(Size::new(size.width, size.height + 1).cast());
+    let mut renderer = Renderer::with_output(Box::new(terminal.clone()));
+
+    renderer.set_color_depth(ColorDepth::TrueColor);
+    renderer.set_size(size);
+
+    (renderer, terminal)
+}
+
+/// Draw a region of the page with BGRA8888 pixels colored from their position
+fn draw_background(renderer: &mut Renderer, rect: Rect, color: impl Fn(u32, u32) -> Color) {
+    receive_bitmap(renderer, rect, color);
+    renderer.draw_damage();
+}
+
+/// Copy a region of a page bitmap colored from pixel positions, without drawing it
+fn receive_bitmap(renderer: &mut Renderer, rect: Rect, color: impl Fn(u32, u32) -> Color) {
+    let size = renderer.get_size() * (4, 8);
+    let pixels = (0..size.height)
+        .flat_map(|y| (0..size.width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| {
+            let color = color(x, y);
+
+            [color.b, color.g, color.r, 255]
+        })
+        .collect::<Vec<_>>();
+
+    renderer.receive_bitmap(&pixels, size, rect);
+}
+
+/// Rectangle of page pixels covering cells
+fn cells(x: i32, y: i32, width: u32, height: u32) -> Rect {
+    Rect::new(x * 4, y * 8, width * 4, height * 8)
+}
+
+/// Assert that every cell of a page region has a color
+fn assert_color(terminal: &VirtualTerminal, rect: Rect, color: Color) {
+    for y in rect.origin.y..rect.origin.y + rect.size.height as i32 {
+        for x in rect.origin.x..rect.origin.x + rect.size.width as i32 {
+            let cell = terminal.cell(x as usize, y as usize + 1);
+
+            assert_eq!(
+                cell.background,
+                VtColor::Rgb(color),
+                "background at {x},{y}"
+            );
+            assert_eq!(
+                cell.foreground,
+                VtColor::Rgb(color),
+                "foreground at {x},{y}"
+            );
+        }
+    }
+}
+
+#[test]
+fn paints_a_flat_background() {
+    let (mut renderer, terminal) = renderer(Size::new(40, 10));
+
+    draw_background(&mut renderer, cells(0, 0, 40, 10), |_, _| RED);
+    renderer.render().unwrap();
+
+    assert_color(&terminal, Rect::new(0, 0, 40, 10), RED);
+
+    for y in 1..=10 {
+        assert_eq!(terminal.row(y), "▄".repeat(40));
+    }
+
+    // The cursor is only shown in focused inputs
+    assert_eq!(terminal.cursor(), None);
+}
+
+#[test]
+fn paints_palette_colors() {
+    let (mut renderer, terminal) = renderer(Size::new(20, 2));
+
+    renderer.set_color_depth(ColorDepth::Xterm256);
+    draw_background(&mut renderer, cells(0, 0, 20, 2), |_, _| RED);
+    renderer.render().unwrap();
+
+    let cell = terminal.cell(0, 1);
+
+    // Red of the 6x6x6 color cube
+    assert_eq!(cell.background, VtColor::Indexed(196));
+    assert_eq!(cell.foreground, VtColor::Indexed(196));
+}
+
+#[test]
+fn dithers_without_repainting_unchanged_cells() {
+    let (mut renderer, terminal) = renderer(Size::new(40, 10));
+    let gradient = |x: u32, y: u32| Color::new(x as u8, (y * 3) as u8, 90);
+
+    renderer.set_color_depth(ColorDepth::Xterm256);
+    renderer.set_dither(Dither::Diffusion);
+    draw_background(&mut renderer, cells(0, 0, 40, 10), gradient);
+
+    let first = renderer.render().unwrap();
+
+    // The error spreading from changed cells only repaints their rows, 3 of 10
+    draw_background(&mut renderer, cells(0, 2, 2, 3), |_, _| BLUE);
+
+    let second = renderer.render().unwrap();
+
+    assert!(
+        second.bytes * 3 < first.bytes,
+        "{} of {} bytes",
+        second.bytes,
+        first.bytes
+    );
+
+    // Neighbors getting different indices are painted again
+    for (index, (_, cell)) in renderer.cells.iter().enumerate().skip(40) {
+        let (background, _) = cell.palette.unwrap();
+
+        assert_eq!(
+            terminal.cell(index % 40, index / 40).background,
+            VtColor::Indexed(background),
+            "cell {index}"
+        );
+    }
+}
+
+#[test]
+fn paints_quadrants() {
+    let (mut renderer, terminal) = renderer(Size::new(24, 4));
+
+    // Red left halves and blue right halves of cells
+    draw_background(&mut renderer, cells(0, 0, 24, 4), |x, _| {
+        if x % 4 < 2 {
+            RED
+        } else {
+            BLUE
+        }
+    });
+    renderer.render().unwrap();
+
+    let cell = terminal.cell(3, 2);
+
+    assert_eq!(cell.text, "▌");
+    assert_eq!(cell.background, VtColor::Rgb(BLUE));
+    assert_eq!(cell.foreground, VtColor::Rgb(RED));
+
+    for y in 1..=4 {
+        assert_eq!(terminal.row(y), "▌".repeat(24));
+    }
+}
+
+#[test]
+fn samples_quadrant_pixel_pairs() {
+    let (mut renderer, terminal) = renderer(Size::new(24, 4));
+
+    // Quadrants average the two pixels at their top left, ignoring odd columns
+    draw_background(&mut renderer, cells(0, 0, 24, 4), |x, y| {
+        if x % 2 == 1 {
+            WHITE
+        } else if y % 8 == 0 {
+            RED
+        } else {
+            BLUE
+        }
+    });
+    renderer.render().unwrap();
+
+    let cell = terminal.cell(3, 2);
+
+    assert_eq!(cell.text, "▄");
+    assert_eq!(cell.background, VtColor::Rgb(Color::new(127, 0, 127)));
+    assert_eq!(cell.foreground, VtColor::Rgb(BLUE));
+}
+
+#[test]
+fn repaints_damaged_regions() {
+    let (mut renderer, terminal) = renderer(Size::new(30, 8));
+
+    draw_background(&mut renderer, cells(0, 0, 30, 8), |_, _| RED);
+    renderer.render().unwrap();
+    draw_background(&mut renderer, cells(5, 2, 10, 3), |_, _| BLUE);
+    renderer.render().unwrap();
+
+    assert_color(&terminal, Rect::new(5, 2, 10, 3), BLUE);
+    assert_color(&terminal, Rect::new(0, 0, 30, 2), RED);
+    assert_color(&terminal, Rect::new(0, 5, 30, 3), RED);
+    assert_color(&terminal, Rect::new(0, 2, 5, 3), RED);
+    assert_color(&terminal, Rect::new(15, 2, 15, 3), RED);
+
+    // Rendering an unchanged frame keeps the grid
+    renderer.render().unwrap();
+
+    assert_color(&terminal, Rect::new(5, 2, 10, 3), BLUE);
+    assert_color(&terminal, Rect::new(15, 2, 15, 3), RED);
+}
+
+#[test]
+fn draws_regions_of_merged_bitmaps() {
+    let (mut renderer, terminal) = renderer(Size::new(30, 8));
+
+    draw_background(&mut renderer, cells(0, 0, 30, 8), |_, _| RED);
+    renderer.render().unwrap();
+
+    // Each bitmap only holds valid pixels in its damaged region
+    receive_bitmap(&mut renderer, cells(0, 0, 10, 2), |_, _| BLUE);
+    receive_bitmap(&mut renderer, cells(20, 6, 10, 2), |x, y| {
+        if x >= 80 && y >= 48 {
+            WHITE
+        } else {
+            Color::black()
+        }
+    });
+    renderer.draw_damage();
+    renderer.render().unwrap();
+
+    assert_color(&terminal, Rect::new(0, 0, 10, 2), BLUE);
+    assert_color(&terminal, Rect::new(20, 6, 10, 2), WHITE);
+    assert_color(&terminal, Rect::new(10, 0, 20, 6), RED);
+    assert_color(&terminal, Rect::new(0, 2, 20, 6), RED);
+}
+
+#[test]
+fn splits_large_unsynchronized_frames() {
+    let noise = |x: u32, y: u32| Color::new((x * 7) as u8, (y * 13) as u8, (x ^ y) as u8);
+
+    // Frames are painted at once when synchronized, or unless split
+    for synchronized in [false, true] {
+        let (mut whole, _) = renderer(Size::new(80, 40));
+
+        if synchronized {
+            whole.enable_synchronized_output();
+        } else {
+            whole.set_split_frames(false);
+        }
+
+        draw_background(&mut whole, cells(0, 0, 80, 40), noise);
+        whole.render().unwrap();
+
+        assert!(!whole.has_pending_frames());
+    }
+
+    // Split by default without synchronized output support
+    let (mut renderer, terminal) = renderer(Size::new(80, 40));
+
+    draw_background(&mut renderer, cells(0, 0, 80, 40), noise);
+
+    let first = renderer.render().unwrap();
+
+    // Rows left over are painted by the next frames
+    assert!(renderer.has_pending_frames());
+    assert!(first.bytes < 20 * 1024, "{} bytes", first.bytes);
+    assert_eq!(terminal.cell(79, 40).background, VtColor::Default);
+
+    while renderer.has_pending_frames() {
+        renderer.render().unwrap();
+    }
+
+    assert_ne!(terminal.cell(79, 40).background, VtColor::Default);
+}
+
+#[test]
+fn paints_text_over_the_background() {
+    let (mut renderer, terminal) = renderer(Size::new(20, 4));
+
+    draw_background(&mut renderer, cells(0, 0, 20, 4), |_, _| BLUE);
+    // Text is positioned from its baseline, in page pixels
+    renderer.draw_text("hello", Point::new(8, 15), Size::splat(0), WHITE);
+    renderer.render().unwrap();
+
+    assert_eq!(terminal.row(2), format!("▄▄hello{}", "▄".repeat(13)));
+
+    for x in 2..7 {
+        let cell = terminal.cell(x, 2);
+
+        assert_eq!(cell.background, VtColor::Rgb(BLUE));
+        assert_eq!(cell.foreground, VtColor::Rgb(WHITE));
+    }
+
+    renderer.clear_text();
+    renderer.render().unwrap();
+
+    assert_eq!(terminal.row(2), "▄".repeat(20));
+    assert_color(&terminal, Rect::new(0, 0, 20, 4), BLUE);
+}
+
+#[test]
+fn paints_wide_graphemes() {
+    let (mut renderer, terminal) = renderer(Size::new(20, 2));
+
+    draw_background(&mut renderer, cells(0, 0, 20, 2), |_, _| BLUE);
+    renderer.draw_text("漢字!", Point::new(0, 7), Size::splat(0), WHITE);
+    renderer.render().unwrap();
+
+    assert_eq!(terminal.cell(0, 1).text, "漢");
+    assert_eq!(terminal.cell(1, 1).text, "");
+    assert_eq!(terminal.cell(2, 1).text, "字");
+    assert_eq!(terminal.row(1), format!("漢字!{}", "▄".repeat(15)));
+}
+
+#[test]
+fn mirrors_frames_to_late_viewers() {
+    let size = Size::new(30, 6);
+    let (mut renderer, terminal) = renderer(size);
+    let path = env::temp_dir().join(format!("carbonyl-test-{}.sock", process::id()));
+
+    renderer.add_mirror(Mirror::Socket(path.clone()).open().unwrap());
+    draw_background(&mut renderer, cells(0, 0, 30, 6), |_, _| BLUE);
+    renderer.draw_text("before", Point::new(0, 15), Size::splat(0), WHITE);
+    renderer.set_title("first").unwrap();
+    renderer.render().unwrap();
+
+    // The viewer joins after the first frame
+    let mut stream = UnixStream::connect(&path).unwrap();
+    let mut viewer = VirtualTerminal::new(Size::new(size.width, size.height + 1).cast());
+
+    draw_background(&mut renderer, cells(10, 3, 5, 2), |_, _| RED);
+    renderer.render().unwrap();
+    renderer.draw_text("after", Point::new(0, 23), Size::splat(0), WHITE);
+    renderer.set_title("second").unwrap();
+    renderer.render().unwrap();
+
+    let mut frames = Vec::new();
+
+    stream.set_nonblocking(true).unwrap();
+
+    match stream.read_to_end(&mut frames) {
+        Err(error) if error.kind() == io::ErrorKind::WouldBlock => (),
+        result => panic!("unexpected read result: {result:?}"),
+    }
+
+    let title = |title: &str| format!("\x1b]2;{title}\x07");
+    let text = String::from_utf8_lossy(&frames);
+
+    // The title is sent with the full frame, then as it changes
+    assert!(matches!(
+        (text.find(&title("first")), text.find(&title("second"))),
+        (Some(first), Some(second)) if first < second
+    ));
+    viewer.write_all(&frames).unwrap();
+
+    for y in 0..=size.height as usize {
+        for x in 0..size.width as usize {
+            assert_eq!(viewer.cell(x, y), terminal.cell(x, y), "cell at {x},{y}");
+        }
+    }
+
+    assert_eq!(viewer.row(3), format!("after{}", "▄".repeat(25)));
+
+    drop(renderer);
+
+    assert!(!path.exists());
+}
+
+#[test]
+fn records_asciinema_sessions() {
+    let size = Size::new(30, 6);
+    let (mut renderer, terminal) = renderer(size);
+    let path = env::temp_dir().join(format!("carbonyl-test-{}.cast", process::id()));
+
+    renderer.add_mirror(Box::new(Recording::create(&path).unwrap()));
+    draw_background(&mut renderer, cells(0, 0, 30, 6), |_, _| BLUE);
+    renderer.draw_text("\"quoted\"", Point::new(0, 15), Size::splat(0), WHITE);
+    renderer.render().unwrap();
+    draw_background(&mut renderer, cells(10, 3, 5, 2), |_, _| RED);
+    renderer.render().unwrap();
+    // Buffered events are written on resizes
+    renderer.set_size(Size::new(40, 10));
+
+    // Replay output events
+    let recording = fs::read_to_string(&path).unwrap();
+    let mut lines = recording.lines();
+    let mut viewer = VirtualTerminal::new(Size::new(size.width, size.height + 1).cast());
+
+    assert!(lines
+        .next()
+        .unwrap()
+        .starts_with(r#"{"version": 2, "width": 30, "height": 7, "timestamp": "#));
+
+    for line in lines.filter(|line| !line.contains(r#", "r", "#)) {
+        let (_, data) = line.split_once(", \"o\", ").unwrap();
+
+        viewer
+            .write_all(json_unescape(&data[..data.len() - 1]).as_bytes())
+            .unwrap();
+    }
+
+    for y in 0..=size.height as usize {
+        for x in 0..size.width as usize {
+            assert_eq!(viewer.cell(x, y), terminal.cell(x, y), "cell at {x},{y}");
+        }
+    }
+
+    assert!(recording.contains(r#", "r", "40x11"]"#));
+
+    // The remaining events are written when the recording is dropped
+    renderer.render().unwrap();
+    drop(renderer);
+
+    let events = fs::read_to_string(&path).unwrap().lines().count();
+
+    assert!(events > recording.lines().count());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn saves_screenshots() {
+    let (mut renderer, _) = renderer(Size::new(20, 2));
+    let path = env::temp_dir().join(format!("carbonyl-test-{}", process::id()));
+    let (ansi, png) = (path.with_extension("ans"), path.with_extension("png"));
+
+    draw_background(&mut renderer, cells(0, 0, 20, 2), |_, _| BLUE);
+    renderer.draw_text("hi", Point::new(0, 7), Size::splat(0), WHITE);
+    renderer.render().unwrap();
+    renderer.save_screenshot(&ansi).unwrap();
+    renderer.save_screenshot(&png).unwrap();
+
+    // Text is replayed with its colors
+    let text = fs::read(&ansi).unwrap();
+    let mut viewer = VirtualTerminal::new(Size::new(20, 4));
+
+    viewer.write_all(&text).unwrap();
+
+    assert_eq!(viewer.row(1), format!("hi{}", "▄".repeat(18)));
+    assert_eq!(viewer.cell(0, 1).foreground, VtColor::Rgb(WHITE));
+    assert_eq!(viewer.cell(5, 2).background, VtColor::Rgb(BLUE));
+
+    // Cells are rasterized as 8x16 pixels
+    let image = fs::read(&png).unwrap();
+
+    assert_eq!(&image[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&image[16..24], [0, 0, 0, 160, 0, 0, 0, 48]);
+
+    fs::remove_file(&ansi).unwrap();
+    fs::remove_file(&png).unwrap();
+
+    // Empty grids are rejected
+    assert!(save_screenshot(&png, &[], 0).is_err());
+}
+
+#[test]
+fn captures_page_bitmaps() {
+    let (mut renderer, _) = renderer(Size::new(20, 2));
+    let path = env::temp_dir().join(format!("carbonyl-test-{}-{{}}.ppm", process::id()));
+    let frame =
+        |index: u32| PathBuf::from(path.to_str().unwrap().replace("{}", &index.to_string()));
+
+    renderer.set_capture(Capture::new(
+        Some(path.clone()),
+        Some(CaptureFrames::Every(2)),
+    ));
+
+    for color in [RED, BLUE, WHITE] {
+        draw_background(&mut renderer, cells(0, 0, 20, 2), |_, _| color);
+    }
+
+    assert!(!frame(1).exists());
+    assert!(!frame(3).exists());
+
+    // The second bitmap is saved as RGB pixels
+    let image = fs::read(frame(2)).unwrap();
+    let header = b"P6\n80 16\n255\n";
+
+    assert_eq!(&image[..header.len()], header);
+    assert_eq!(image.len(), header.len() + 80 * 16 * 3);
+    assert_eq!(&image[header.len()..header.len() + 3], [0, 0, 255]);
+
+    fs::remove_file(frame(2)).unwrap();
+
+    // Paths without a frame number get one when capturing every nth bitmap
+    let single = env::temp_dir().join(format!("carbonyl-test-{}.ppm", process::id()));
+    let numbered = env::temp_dir().join(format!("carbonyl-test-{}-2.ppm", process::id()));
+
+    renderer.set_capture(Capture::new(Some(single), Some(CaptureFrames::Every(2))));
+
+    for color in [RED, BLUE] {
+        draw_background(&mut renderer, cells(0, 0, 20, 2), |_, _| color);
+    }
+
+    fs::remove_file(numbered).unwrap();
+}
+
+/// Decode a JSON string literal
+fn json_unescape(json: &str) -> String {
+    let mut chars = json
+        .strip_prefix('"')
+        .unwrap()
+        .strip_suffix('"')
+        .unwrap()
+        .chars();
+    let mut value = String::new();
+
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            value.push(char);
+
+            continue;
+        }
+
+        match chars.next().unwrap() {
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            'u' => {
+                let code = chars.by_ref().take(4).collect::<String>();
+
+                value.push(char::from_u32(u32::from_str_radix(&code, 16).unwrap()).unwrap())
+            }
+            char => value.push(char),
+        }
+    }
+
+    value
+}
@@ -0,0 +1,207 @@
+use std::{ffi::OsStr, fs, io, path::Path};
+
+use crate::{
+    gfx::{Color, Size},
+    utils::png,
+};
+
+use super::{braille_mask, octant_mask, sextant_mask, Cell};
+
+/// Size of a cell in screenshot pixels
+const CELL_SIZE: Size<usize> = Size::new(8, 16);
+
+/// Save cells as an image if the path ends with `.png`, or as text using SGR sequences otherwise.
+pub fn save_screenshot(path: &Path, cells: &[&Cell], width: usize) -> io::Result<()> {
+    if width == 0 || cells.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no cells to save",
+        ));
+    }
+
+    let data = match path.extension().and_then(OsStr::to_str) {
+        Some("png") => rasterize(cells, width),
+        _ => ansi(cells, width),
+    };
+
+    fs::write(path, data)
+}
+
+/// Colors and characters drawn in a cell
+fn appearance(cell: &Cell) -> (Color, Color, Option<&str>) {
+    match cell.grapheme {
+        // Cells covered by wide graphemes
+        Some(ref grapheme) if grapheme.index > 0 => (cell.background, grapheme.color, None),
+        Some(ref grapheme) => (cell.background, grapheme.color, Some(&grapheme.char)),
+        None => (cell.background, cell.foreground, None),
+    }
+}
+
+/// Write cells as rows of text, using true color SGR sequences
+fn ansi(cells: &[&Cell], width: usize) -> Vec<u8> {
+    let mut out = String::new();
+
+    for row in cells.chunks(width) {
+        let mut colors = None;
+
+        for cell in row {
+            let (background, foreground, text) = appearance(cell);
+            let char = char::from_u32(cell.codepoint).unwrap_or(' ');
+
+            if text.is_none() && cell.grapheme.is_some() {
+                continue;
+            }
+
+            if colors != Some((background, foreground)) {
+                colors = Some((background, foreground));
+                out += &format!(
+                    "\x1b[48;2;{};{};{}m\x1b[38;2;{};{};{}m",
+                    background.r,
+                    background.g,
+                    background.b,
+                    foreground.r,
+                    foreground.g,
+                    foreground.b
+                );
+            }
+
+            match text {
+                Some(text) => out += text,
+                None => out.push(char),
+            }
+        }
+
+        out += "\x1b[0m\r\n";
+    }
+
+    out.into_bytes()
+}
+
+/// Rasterize cells as a PNG image.
+///
+/// Block elements, sextants, octants, Braille patterns and wedges are drawn from their shape,
+/// text is drawn as boxes of blended colors.
+fn rasterize(cells: &[&Cell], width: usize) -> Vec<u8> {
+    let size = Size::new(width, cells.len() / width) * CELL_SIZE;
+    let mut rgb = vec![0; size.width * size.height * 3];
+
+    for (index, cell) in cells.iter().enumerate() {
+        let (background, foreground, text) = appearance(cell);
+        let char = char::from_u32(cell.codepoint).unwrap_or(' ');
+        let origin = Size::new(index % width, index / width) * CELL_SIZE;
+
+        for y in 0..CELL_SIZE.height {
+            let row = ((origin.height + y) * size.width + origin.width) * 3;
+
+            for x in 0..CELL_SIZE.width {
+                // Coverage at the pixel center
+                let (cx, cy) = (
+                    (x as f32 + 0.5) / CELL_SIZE.width as f32,
+                    (y as f32 + 0.5) / CELL_SIZE.height as f32,
+                );
+                let coverage = match text {
+                    Some(text) if text.trim().is_empty() => 0.0,
+                    Some(_) => text_coverage(cx, cy),
+                    None if cell.grapheme.is_some() => 0.0,
+                    None => glyph_coverage(char, cx, cy),
+                };
+                let color = background.cast::<f32>()
+                    + (foreground.cast::<f32>() - background.cast::<f32>()) * coverage;
+                let pixel = row + x * 3;
+
+                rgb[pixel..pixel + 3].copy_from_slice(&[
+                    color.r.round() as u8,
+                    color.g.round() as u8,
+                    color.b.round() as u8,
+                ]);
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+
+    png::encode(&rgb, size.width, size.height, &mut out);
+
+    out
+}
+
+/// Coverage of text, drawn as a box of half the foreground over the x-height
+fn text_coverage(x: f32, y: f32) -> f32 {
+    if (0.125..0.875).contains(&x) && (0.3125..0.8125).contains(&y) {
+        0.5
+    } else {
+        0.0
+    }
+}
+
+/// Coverage of a glyph at a point of a cell, in normalized coordinates
+fn glyph_coverage(char: char, x: f32, y: f32) -> f32 {
+    let (left, top) = (x < 0.5, y < 0.5);
+    // Whether a block of a grid of `columns` by `rows` is set in a row-major mask
+    let grid = |mask: u32, columns: f32, rows: f32| {
+        let index = (y * rows) as u32 * columns as u32 + (x * columns) as u32;
+
+        mask & 1 << index != 0
+    };
+    let covered = match char {
+        '█' => true,
+        '▀' => top,
+        '▐' => !left,
+        '▔' => y < 0.125,
+        '▕' => x >= 0.875,
+        // Lower eighths
+        '▁'..='▇' => y >= 1.0 - (char as u32 - 0x2580) as f32 / 8.0,
+        // Left eighths
+        '▉'..='▏' => x < (0x2590 - char as u32) as f32 / 8.0,
+        // Shades
+        '░' => return 0.25,
+        '▒' => return 0.5,
+        '▓' => return 0.75,
+        // Upper quarters and middle quarters, used for octant patterns
+        '\u{1fb82}' => y < 0.25,
+        '\u{1fb85}' => y < 0.75,
+        '\u{1fbe6}' => left && (0.25..0.75).contains(&y),
+        '\u{1fbe7}' => !left && (0.25..0.75).contains(&y),
+        // Quadrants, as masks in row-major order on a 2x2 grid
+        '▖'..='▟' => {
+            const QUADRANTS: [u32; 10] = [
+                0b0100, 0b1000, 0b0001, 0b1101, 0b1001, 0b0111, 0b1011, 0b0010, 0b0110, 0b1110,
+            ];
+
+            grid(QUADRANTS[(char as u32 - 0x2596) as usize], 2.0, 2.0)
+        }
+        // Wedges
+        '◢' => x + y > 1.0,
+        '◣' => y > x,
+        '◤' => x + y < 1.0,
+        '◥' => x > y,
+        '🬿' => 3.0 * y > 1.0 + 2.0 * x,
+        '🭀' => y > 2.0 * x,
+        '🭊' => 3.0 * y > 3.0 - 2.0 * x,
+        '🭋' => y > 2.0 - 2.0 * x,
+        '🭬' => x < y && x < 1.0 - y,
+        '🭭' => y < x && y < 1.0 - x,
+        '🭮' => x > y && x > 1.0 - y,
+        '🭯' => y > x && y > 1.0 - x,
+        char => {
+            if let Some(mask) = sextant_mask(char) {
+                grid(mask, 2.0, 3.0)
+            } else if let Some(mask) = octant_mask(char) {
+                grid(mask, 2.0, 4.0)
+            } else if let Some(mask) = braille_mask(char) {
+                // Dots are drawn at the center of their block
+                let (dx, dy) = ((x * 2.0).fract(), (y * 4.0).fract());
+
+                grid(mask, 2.0, 4.0) && (0.25..0.75).contains(&dx) && (0.25..0.75).contains(&dy)
+            } else {
+                false
+            }
+        }
+    };
+
+    if covered {
+        1.0
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    os::unix::{
+        fs::FileTypeExt,
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use libc::c_void;
+
+use crate::{gfx::Size, utils::log};
+
+/// Maximum time spent sending a frame to a viewer before disconnecting it
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A destination of the bytes of painted frames.
+pub trait FrameSink {
+    /// Write the sequences painting a frame
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()>;
+
+    /// Whether the sink started from an unknown terminal state,
+    /// and is waiting for a frame painting every cell
+    fn needs_full_frame(&mut self) -> bool {
+        false
+    }
+
+    /// Write a frame painting every cell, after which frames can be written
+    fn write_full_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.write_frame(frame)
+    }
+
+    /// Called when the terminal is resized, with its size in cells
+    fn resize(&mut self, _size: Size) {}
+}
+
+impl<W: Write> FrameSink for W {
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.write_all(frame)?;
+        self.flush()
+    }
+}
+
+/// A copy of the painted frames, for recording or remote viewing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mirror {
+    /// Frames written to a file
+    File(PathBuf),
+    /// Frames sent to viewers connecting to a Unix domain socket
+    Socket(PathBuf),
+}
+
+impl Mirror {
+    pub fn open(&self) -> io::Result<Box<dyn FrameSink>> {
+        Ok(match self {
+            Mirror::File(path) => Box::new(FileSink {
+                file: File::create(path)?,
+                started: false,
+            }),
+            Mirror::Socket(path) => Box::new(SocketSink::bind(path.clone())?),
+        })
+    }
+}
+
+impl FromStr for Mirror {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.strip_prefix("unix:") {
+            Some("") => Err(()),
+            Some(path) => Ok(Self::Socket(path.into())),
+            None if value.is_empty() => Err(()),
+            None => Ok(Self::File(value.into())),
+        }
+    }
+}
+
+/// Writes frames to a file, starting with a full frame.
+struct FileSink {
+    file: File,
+    started: bool,
+}
+
+impl FrameSink for FileSink {
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        if self.started {
+            self.file.write_all(frame)?;
+        }
+
+        Ok(())
+    }
+
+    fn needs_full_frame(&mut self) -> bool {
+        !self.started
+    }
+
+    fn write_full_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.started = true;
+        self.file.write_all(frame)
+    }
+}
+
+/// Sends frames to read-only viewers connected to a Unix domain socket,
+/// for instance using `socat UNIX-CONNECT:<path> STDOUT`.
+///
+/// Viewers joining a session receive a full frame first.
+/// Viewers that disconnect or cannot keep up are dropped.
+pub struct SocketSink {
+    path: PathBuf,
+    listener: UnixListener,
+    viewers: Vec<UnixStream>,
+    /// Viewers waiting for a full frame
+    joining: Vec<UnixStream>,
+}
+
+impl SocketSink {
+    pub fn bind(path: PathBuf) -> io::Result<SocketSink> {
+        // Replace a socket left by a previous session, but nothing else
+        if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+
+        listener.set_nonblocking(true)?;
+
+        Ok(SocketSink {
+            path,
+            listener,
+            viewers: Vec::new(),
+            joining: Vec::new(),
+        })
+    }
+
+    /// Accept pending connections
+    fn accept(&mut self) {
+        while let Ok((viewer, _)) = self.listener.accept() {
+            match prepare(&viewer) {
+                Ok(()) => {
+                    log::debug!("mirror: viewer connected to {:?}", self.path);
+
+                    self.joining.push(viewer)
+                }
+                Err(error) => log::debug!("mirror: failed to set up viewer: {}", error),
+            }
+        }
+    }
+}
+
+impl FrameSink for SocketSink {
+    fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.viewers.retain(|viewer| send(viewer, frame));
+
+        Ok(())
+    }
+
+    fn needs_full_frame(&mut self) -> bool {
+        self.accept();
+
+        !self.joining.is_empty()
+    }
+
+    fn write_full_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        for viewer in self.joining.drain(..) {
+            if send(&viewer, frame) {
+                self.viewers.push(viewer)
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for SocketSink {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Configure a viewer connection for blocking writes with a timeout
+fn prepare(viewer: &UnixStream) -> io::Result<()> {
+    viewer.set_nonblocking(false)?;
+    viewer.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    // Disconnected viewers should not raise SIGPIPE
+    #[cfg(target_os = "macos")]
+    unsafe {
+        let enable: libc::c_int = 1;
+
+        libc::setsockopt(
+            viewer.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_NOSIGPIPE,
+            &enable as *const _ as *const c_void,
+            std::mem::size_of_val(&enable) as libc::socklen_t,
+        );
+    }
+
+    Ok(())
+}
+
+/// Send a frame to a viewer, returns `false` if the viewer should be dropped
+fn send(viewer: &UnixStream, mut bytes: &[u8]) -> bool {
+    #[cfg(target_os = "linux")]
+    const FLAGS: libc::c_int = libc::MSG_NOSIGNAL;
+    #[cfg(not(target_os = "linux"))]
+    const FLAGS: libc::c_int = 0;
+
+    while !bytes.is_empty() {
+        let sent = unsafe {
+            libc::send(
+                viewer.as_raw_fd(),
+                bytes.as_ptr() as *const c_void,
+                bytes.len(),
+                FLAGS,
+            )
+        };
+
+        if sent < 0 {
+            let error = io::Error::last_os_error();
+
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+
+            log::debug!("mirror: dropping viewer: {}", error);
+
+            return false;
+        }
+
+        bytes = &bytes[sent as usize..];
+    }
+
+    true
+}
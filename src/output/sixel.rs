@@ -0,0 +1,132 @@
+use std::io::{self, Write};
+
+use super::quantizer::Palette;
+
+/// Encode an indexed image as a sixel sequence.
+///
+/// `indices` contains `width * height` palette indices, row by row.
+pub fn encode<W: Write>(
+    out: &mut W,
+    palette: &Palette,
+    indices: &[u8],
+    width: usize,
+    height: usize,
+) -> io::Result<()> {
+    // Enter sixel mode, keeping a 1:1 pixel aspect ratio
+    write!(out, "\x1bP0;1;0q\"1;1;{width};{height}")?;
+
+    // Define the color registers, using percentages
+    for (index, color) in palette.colors().iter().enumerate() {
+        let (r, g, b) = (color.cast::<u32>() * 100 / 255).into();
+
+        write!(out, "#{index};2;{r};{g};{b}")?;
+    }
+
+    let colors = palette.colors().len();
+    let mut used = vec![false; colors];
+    let mut bits = vec![0u8; colors * width];
+
+    // A sixel band contains 6 rows of pixels
+    for top in (0..height).step_by(6) {
+        let bottom = (top + 6).min(height);
+
+        used.fill(false);
+        bits.fill(0);
+
+        for y in top..bottom {
+            let row = &indices[y * width..(y + 1) * width];
+            let bit = 1 << (y - top);
+
+            for (x, &index) in row.iter().enumerate() {
+                used[index as usize] = true;
+                bits[index as usize * width + x] |= bit;
+            }
+        }
+
+        let mut first = true;
+
+        for (index, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+            // Go back to the start of the band for every color but the first
+            if !first {
+                out.write_all(b"$")?;
+            }
+
+            first = false;
+            write!(out, "#{index}")?;
+            write_run_length(out, &bits[index * width..(index + 1) * width])?;
+        }
+
+        // Move to the next band
+        out.write_all(b"-")?;
+    }
+
+    // Exit sixel mode
+    out.write_all(b"\x1b\\")
+}
+
+/// Write a row of sixels using run-length encoding, omitting trailing blanks.
+fn write_run_length<W: Write>(out: &mut W, row: &[u8]) -> io::Result<()> {
+    let end = row.iter().rposition(|&bits| bits != 0).map_or(0, |x| x + 1);
+    let mut iter = row[..end].iter().peekable();
+
+    while let Some(&bits) = iter.next() {
+        let mut count = 1;
+
+        while iter.next_if_eq(&&bits).is_some() {
+            count += 1;
+        }
+
+        let char = bits + 0x3f;
+
+        match count {
+            1 => out.write_all(&[char])?,
+            2 => out.write_all(&[char, char])?,
+            3 => out.write_all(&[char, char, char])?,
+            count => write!(out, "!{count}{}", char as char)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gfx::Color;
+
+    use super::*;
+
+    #[test]
+    fn encodes_bands_of_run_lengths() {
+        let palette = Palette::new(vec![Color::black(), Color::new(255, 255, 255)]);
+        // Black image with a white pixel at the end of the first row, and a white last row
+        let mut indices = vec![0; 6 * 7];
+
+        indices[5] = 1;
+        indices[36..].fill(1);
+
+        let mut out = Vec::new();
+
+        encode(&mut out, &palette, &indices, 6, 7).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "\x1bP0;1;0q\"1;1;6;7#0;2;0;0;0#1;2;100;100;100",
+                // First band, black then white from its start
+                "#0!5~}$#1!5?@-",
+                // Second band, a single row
+                "#1!6@-",
+                "\x1b\\"
+            )
+        );
+    }
+
+    #[test]
+    fn repeats_short_runs() {
+        let mut out = Vec::new();
+
+        write_run_length(&mut out, &[1, 1, 2, 2, 2, 3, 0, 0]).unwrap();
+
+        assert_eq!(out, b"@@AAAB");
+    }
+}
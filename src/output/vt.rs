@@ -0,0 +1,336 @@
+use std::{cell::RefCell, io, mem, rc::Rc};
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::gfx::{Color, Point, Size};
+
+/// Color of a virtual terminal cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VtColor {
+    Default,
+    Indexed(u8),
+    Rgb(Color),
+}
+
+/// A cell of the virtual terminal grid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VtCell {
+    /// Grapheme drawn in the cell, empty after a wide character
+    pub text: String,
+    pub background: VtColor,
+    pub foreground: VtColor,
+    pub reverse: bool,
+}
+
+/// An in-process terminal, maintaining a grid of cells from the bytes written to it.
+///
+/// Supports the sequences written by `Painter`: cursor movements, erasing,
+/// repeating characters and colors. Other sequences and strings are ignored.
+#[derive(Clone)]
+pub struct VirtualTerminal {
+    screen: Rc<RefCell<Screen>>,
+}
+
+impl VirtualTerminal {
+    pub fn new(size: Size<usize>) -> VirtualTerminal {
+        VirtualTerminal {
+            screen: Rc::new(RefCell::new(Screen::new(size))),
+        }
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> VtCell {
+        let screen = self.screen.borrow();
+
+        screen.cells[y * screen.size.width + x].clone()
+    }
+
+    /// Get the text of a row
+    pub fn row(&self, y: usize) -> String {
+        let screen = self.screen.borrow();
+        let width = screen.size.width;
+
+        screen.cells[y * width..(y + 1) * width]
+            .iter()
+            .map(|cell| cell.text.as_str())
+            .collect()
+    }
+
+    /// Get the cursor position, if visible
+    pub fn cursor(&self) -> Option<Point<usize>> {
+        let screen = self.screen.borrow();
+
+        screen.cursor_visible.then_some(screen.cursor)
+    }
+}
+
+impl io::Write for VirtualTerminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut screen = self.screen.borrow_mut();
+
+        for &byte in buf {
+            screen.feed(byte)
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Attributes applied to printed and erased cells.
+#[derive(Clone, Copy)]
+struct Pen {
+    background: VtColor,
+    foreground: VtColor,
+    reverse: bool,
+}
+
+impl Pen {
+    const DEFAULT: Pen = Pen {
+        background: VtColor::Default,
+        foreground: VtColor::Default,
+        reverse: false,
+    };
+
+    fn cell(&self, text: &str) -> VtCell {
+        VtCell {
+            text: text.to_owned(),
+            background: self.background,
+            foreground: self.foreground,
+            reverse: self.reverse,
+        }
+    }
+}
+
+enum State {
+    Ground,
+    Escape,
+    /// Parameters and intermediate bytes of a control sequence
+    Csi(Vec<u8>),
+    /// OSC, DCS or APC string, ignored until terminated
+    String {
+        escape: bool,
+    },
+}
+
+struct Screen {
+    size: Size<usize>,
+    cells: Vec<VtCell>,
+    /// Cursor position, `x` being equal to the width after writing to the last column
+    cursor: Point<usize>,
+    cursor_visible: bool,
+    pen: Pen,
+    /// Last printed character, repeated by REP
+    last: Option<char>,
+    state: State,
+    /// Bytes of an incomplete UTF-8 character
+    utf8: Vec<u8>,
+}
+
+impl Screen {
+    fn new(size: Size<usize>) -> Screen {
+        Screen {
+            size,
+            cells: vec![Pen::DEFAULT.cell(" "); size.width * size.height],
+            cursor: Point::new(0, 0),
+            cursor_visible: true,
+            pen: Pen::DEFAULT,
+            last: None,
+            state: State::Ground,
+            utf8: Vec::new(),
+        }
+    }
+
+    fn feed(&mut self, byte: u8) {
+        self.state = match mem::replace(&mut self.state, State::Ground) {
+            State::Ground => match byte {
+                0x1b => State::Escape,
+                b'\r' => {
+                    self.cursor.x = 0;
+
+                    State::Ground
+                }
+                b'\n' => {
+                    self.line_feed();
+
+                    State::Ground
+                }
+                0x00..=0x1f => State::Ground,
+                _ => {
+                    self.utf8.push(byte);
+
+                    match std::str::from_utf8(&self.utf8) {
+                        Ok(text) => {
+                            let text = text.to_owned();
+
+                            self.utf8.clear();
+                            text.chars().for_each(|char| self.print(char));
+                        }
+                        Err(error) if error.error_len().is_some() => self.utf8.clear(),
+                        Err(_) => (),
+                    }
+
+                    State::Ground
+                }
+            },
+            State::Escape => match byte {
+                b'[' => State::Csi(Vec::new()),
+                b']' | b'P' | b'_' | b'^' => State::String { escape: false },
+                _ => State::Ground,
+            },
+            State::Csi(mut params) => match byte {
+                0x40..=0x7e => {
+                    self.csi(&params, byte);
+
+                    State::Ground
+                }
+                _ => {
+                    params.push(byte);
+
+                    State::Csi(params)
+                }
+            },
+            State::String { escape } => match byte {
+                0x07 => State::Ground,
+                b'\\' if escape => State::Ground,
+                _ => State::String {
+                    escape: byte == 0x1b,
+                },
+            },
+        }
+    }
+
+    fn csi(&mut self, params: &[u8], code: u8) {
+        if let Some(b'?') = params.first() {
+            if params == b"?25" {
+                self.cursor_visible = code == b'h'
+            }
+
+            return;
+        }
+
+        let params = String::from_utf8_lossy(params)
+            .split(';')
+            .map(|param| param.parse::<usize>().unwrap_or(0))
+            .collect::<Vec<_>>();
+        let count = params[0].max(1);
+        let Size { width, height } = self.size;
+
+        // Movements from past the last column start from the last column
+        if code != b'm' && code != b'b' {
+            self.cursor.x = self.cursor.x.min(width - 1);
+        }
+
+        match code {
+            b'H' | b'f' => {
+                let y = params[0].max(1) - 1;
+                let x = params.get(1).copied().unwrap_or(1).max(1) - 1;
+
+                self.cursor = Point::new(x.min(width - 1), y.min(height - 1));
+            }
+            b'A' => self.cursor.y = self.cursor.y.saturating_sub(count),
+            b'B' => self.cursor.y = (self.cursor.y + count).min(height - 1),
+            b'C' => self.cursor.x = (self.cursor.x + count).min(width - 1),
+            b'D' => self.cursor.x = self.cursor.x.saturating_sub(count),
+            b'K' => self.erase(width - self.cursor.x),
+            b'X' => self.erase(count.min(width - self.cursor.x)),
+            b'b' => {
+                if let Some(char) = self.last {
+                    (0..count).for_each(|_| self.print(char))
+                }
+            }
+            b'm' => self.sgr(&params),
+            _ => (),
+        }
+    }
+
+    /// Erase cells using the current background, without moving the cursor
+    fn erase(&mut self, count: usize) {
+        let start = self.cursor.y * self.size.width + self.cursor.x;
+        let blank = Pen {
+            reverse: false,
+            ..self.pen
+        }
+        .cell(" ");
+
+        self.cells[start..start + count].fill(blank);
+    }
+
+    fn sgr(&mut self, params: &[usize]) {
+        let mut params = params.iter().copied();
+
+        while let Some(param) = params.next() {
+            let mut extended = || match params.next() {
+                Some(5) => VtColor::Indexed(params.next().unwrap_or(0) as u8),
+                Some(2) => {
+                    let mut channel = || params.next().unwrap_or(0) as u8;
+
+                    VtColor::Rgb(Color::new(channel(), channel(), channel()))
+                }
+                _ => VtColor::Default,
+            };
+
+            match param {
+                0 => self.pen = Pen::DEFAULT,
+                7 => self.pen.reverse = true,
+                27 => self.pen.reverse = false,
+                30..=37 => self.pen.foreground = VtColor::Indexed(param as u8 - 30),
+                90..=97 => self.pen.foreground = VtColor::Indexed(param as u8 - 90 + 8),
+                40..=47 => self.pen.background = VtColor::Indexed(param as u8 - 40),
+                100..=107 => self.pen.background = VtColor::Indexed(param as u8 - 100 + 8),
+                38 => self.pen.foreground = extended(),
+                48 => self.pen.background = extended(),
+                39 => self.pen.foreground = VtColor::Default,
+                49 => self.pen.background = VtColor::Default,
+                _ => (),
+            }
+        }
+    }
+
+    fn print(&mut self, char: char) {
+        let width = char.width().unwrap_or(0);
+
+        if width == 0 {
+            // Combining characters join the previous grapheme
+            let (x, y) = (self.cursor.x, self.cursor.y);
+            let cell = self.cells[y * self.size.width..y * self.size.width + x]
+                .iter_mut()
+                .rev()
+                .find(|cell| !cell.text.is_empty());
+
+            if let Some(cell) = cell {
+                cell.text.push(char)
+            }
+
+            return;
+        }
+
+        if self.cursor.x + width > self.size.width {
+            self.cursor.x = 0;
+            self.line_feed();
+        }
+
+        let index = self.cursor.y * self.size.width + self.cursor.x;
+
+        self.cells[index] = self.pen.cell(char.encode_utf8(&mut [0; 4]));
+
+        for cell in &mut self.cells[index + 1..index + width] {
+            *cell = self.pen.cell("");
+        }
+
+        self.cursor.x += width;
+        self.last = Some(char);
+    }
+
+    fn line_feed(&mut self) {
+        if self.cursor.y + 1 < self.size.height {
+            self.cursor.y += 1;
+        } else {
+            self.cells.drain(..self.size.width);
+            self.cells
+                .resize(self.size.width * self.size.height, Pen::DEFAULT.cell(" "));
+        }
+    }
+}
@@ -12,6 +12,8 @@ pub struct Window {
     pub scale: Size<f32>,
     /// Size of the termina window in cells
     pub cells: Size,
+    /// Size of a terminal cell in device pixels, if reported with the window size
+    pub cell_size: Option<Size>,
     /// Size of the browser window in pixels
     pub browser: Size,
     /// Command line arguments
@@ -25,6 +27,7 @@ impl Window {
             dpi: 1.0,
             scale: (0.0, 0.0).into(),
             cells: (0, 0).into(),
+            cell_size: None,
             browser: (0, 0).into(),
             cmd: CommandLine::parse(),
         };
@@ -50,6 +53,11 @@ impl Window {
             }
         };
 
+        if cell.width > 0 && cell.height > 0 && term.width > 0 && term.height > 0 {
+            self.cell_size =
+                Some(Size::new(cell.width / term.width, cell.height / term.height).cast());
+        }
+
         if cell.width == 0 || cell.height == 0 {
             cell.width = 8;
             cell.height = 16;
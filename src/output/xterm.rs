@@ -1,25 +1,195 @@
 use crate::gfx::Color;
 
+use super::kd_tree::KDTree;
+
+/// Bits per channel used to index the nearest color cache
+const CACHE_BITS: u32 = 6;
+/// Number of ANSI colors at the start of the palette, usually themed by the terminal
+const ANSI_COLORS: u8 = 16;
+
+/// The xterm 256 colors palette, with caches for perceptual nearest color lookups.
+///
+/// Themed colors use the xterm defaults until reported by the terminal.
+#[derive(Clone)]
+pub struct XtermPalette {
+    colors: Vec<Color>,
+    foreground: Color,
+    background: Color,
+    xterm: Lookup,
+    ansi: Lookup,
+}
+
+impl XtermPalette {
+    pub fn new() -> Self {
+        Self {
+            colors: (0..=255).map(Color::from_xterm).collect(),
+            foreground: Color::from_xterm(7),
+            background: Color::from_xterm(0),
+            xterm: Lookup::new((0..=255).collect()),
+            ansi: Lookup::new((0..ANSI_COLORS).collect()),
+        }
+    }
+
+    /// Get the color of a palette index
+    pub fn color(&self, code: u8) -> Color {
+        self.colors[code as usize]
+    }
+
+    /// Get the default foreground color of the terminal
+    pub fn foreground(&self) -> Color {
+        self.foreground
+    }
+
+    /// Get the default background color of the terminal
+    pub fn background(&self) -> Color {
+        self.background
+    }
+
+    /// Set a palette color reported by the terminal
+    pub fn set_color(&mut self, code: u8, color: Color) {
+        self.colors[code as usize] = color;
+        self.xterm.reset();
+        self.ansi.reset();
+    }
+
+    pub fn set_foreground(&mut self, color: Color) {
+        self.foreground = color
+    }
+
+    pub fn set_background(&mut self, color: Color) {
+        self.background = color
+    }
+
+    /// Find the index of the perceptually closest color to `color` on the palette
+    pub fn index_of(&mut self, color: Color) -> u8 {
+        self.xterm.nearest(&self.colors, color)
+    }
+
+    /// Find the index of the perceptually closest color to `color` in the first 16 colors
+    pub fn ansi_index_of(&mut self, color: Color) -> u8 {
+        self.ansi.nearest(&self.colors, color)
+    }
+}
+
+impl Default for XtermPalette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Nearest color search on a subset of the palette, built lazily.
+#[derive(Clone)]
+struct Lookup {
+    codes: Vec<u8>,
+    tree: Option<KDTree>,
+    cache: Vec<u16>,
+}
+
+impl Lookup {
+    fn new(codes: Vec<u8>) -> Self {
+        Self {
+            codes,
+            tree: None,
+            cache: Vec::new(),
+        }
+    }
+
+    /// Rebuild the tree and the cache on the next lookup
+    fn reset(&mut self) {
+        self.tree = None
+    }
+
+    fn nearest(&mut self, colors: &[Color], color: Color) -> u8 {
+        let tree = self.tree.get_or_insert_with(|| {
+            let oklab = self
+                .codes
+                .iter()
+                .map(|&code| colors[code as usize].to_oklab())
+                .collect::<Vec<_>>();
+
+            self.cache.clear();
+            self.cache.resize(1 << (CACHE_BITS * 3), u16::MAX);
+
+            KDTree::new(&oklab)
+        });
+        let shift = 8 - CACHE_BITS;
+        let key = (color.r as usize >> shift) << (CACHE_BITS * 2)
+            | (color.g as usize >> shift) << CACHE_BITS
+            | (color.b as usize >> shift);
+
+        if self.cache[key] == u16::MAX {
+            // Use the center of the cache bucket so results do not depend on lookup order
+            let center = color.map(|channel| channel >> shift << shift | 1 << (shift - 1));
+            let nearest = tree.nearest(center.to_oklab()).unwrap_or(0);
+
+            self.cache[key] = self.codes[nearest] as u16;
+        }
+
+        self.cache[key] as u8
+    }
+}
+
 impl Color {
-    pub fn to_xterm(&self) -> u8 {
-        if self.max_val() - self.min_val() < 8 {
-            match self.r {
-                0..=4 => 16,
-                5..=8 => 232,
-                238..=246 => 255,
-                247..=255 => 231,
-                r => 232 + (r - 8 + 5) / 10,
+    /// Get the color of an index of the xterm 256 colors palette
+    pub fn from_xterm(code: u8) -> Color {
+        const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+        const ANSI: [(u8, u8, u8); 16] = [
+            (0, 0, 0),
+            (128, 0, 0),
+            (0, 128, 0),
+            (128, 128, 0),
+            (0, 0, 128),
+            (128, 0, 128),
+            (0, 128, 128),
+            (192, 192, 192),
+            (128, 128, 128),
+            (255, 0, 0),
+            (0, 255, 0),
+            (255, 255, 0),
+            (0, 0, 255),
+            (255, 0, 255),
+            (0, 255, 255),
+            (255, 255, 255),
+        ];
+
+        match code {
+            0..=15 => ANSI[code as usize].into(),
+            16..=231 => {
+                let index = code as usize - 16;
+
+                Color::new(LEVELS[index / 36], LEVELS[index / 6 % 6], LEVELS[index % 6])
             }
-        } else {
-            let scale = 5.0 / 200.0;
-
-            (16.0
-                + self
-                    .cast::<f32>()
-                    .mul_add(scale, scale * -55.0)
-                    .max(0.0)
-                    .round()
-                    .dot((36.0, 6.0, 1.0))) as u8
+            _ => Color::splat(8 + (code - 232) * 10),
         }
     }
+
+    /// Perceived brightness of the color, from 0 to 255
+    pub fn luminance(&self) -> u8 {
+        (self.cast::<u32>().dot((299, 587, 114)) / 1000) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_default_ansi_colors() {
+        let mut palette = XtermPalette::new();
+
+        assert_eq!(palette.index_of(Color::new(128, 0, 0)), 1);
+        assert_eq!(palette.index_of(Color::new(192, 192, 192)), 7);
+    }
+
+    #[test]
+    fn matches_reported_ansi_colors() {
+        let mut palette = XtermPalette::new();
+
+        palette.index_of(Color::new(128, 0, 0));
+        palette.set_color(1, Color::new(10, 20, 30));
+        palette.set_color(4, Color::new(128, 0, 0));
+
+        assert_eq!(palette.index_of(Color::new(10, 20, 30)), 1);
+        assert_eq!(palette.index_of(Color::new(128, 0, 0)), 4);
+    }
 }
@@ -1,6 +1,6 @@
 use std::env;
 
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::{
     gfx::{Color, Point, Size},
@@ -15,6 +15,20 @@ pub enum NavigationAction {
     GoBack(),
     GoForward(),
     Refresh(),
+    /// Switch to the next render backend, handled by the renderer
+    CycleBackend(),
+    /// Save a screenshot of the cells, handled by the renderer
+    Screenshot(),
+    /// Save the page pixels, handled by the renderer
+    Capture(),
+}
+
+/// Whether the modifier used for browser shortcuts is pressed
+pub fn shortcut_modifier(key: &Key) -> bool {
+    match env::consts::OS {
+        "macos" => key.modifiers.meta,
+        _ => key.modifiers.alt,
+    }
 }
 
 #[derive(Debug)]
@@ -27,11 +41,18 @@ pub struct NavigationElement {
 pub struct Navigation {
     url: Option<String>,
     size: Size,
+    /// Position of the cursor in the URL, in characters
     cursor: Option<usize>,
     can_go_back: bool,
     can_go_forward: bool,
 }
 
+impl Default for Navigation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Navigation {
     pub fn new() -> Self {
         Self {
@@ -44,48 +65,46 @@ impl Navigation {
     }
 
     pub fn cursor(&self) -> Option<Point> {
-        Some((11 + self.cursor? as i32, 0).into())
+        let url = self.display_url();
+        let column = url[..byte_index(url, self.cursor?)].width();
+
+        Some((11 + column as i32, 0).into())
     }
 
     pub fn keypress(&mut self, key: &Key) -> NavigationAction {
-        let modifier_key = match env::consts::OS {
-            "macos" => key.modifiers.meta,
-            _ => key.modifiers.alt,
-        };
-
         match self.cursor {
-            None => match (modifier_key, key.char) {
-                (true, 0x14) => NavigationAction::GoBack(),
-                (true, 0x13) => NavigationAction::GoForward(),
+            None => match (shortcut_modifier(key), key.char) {
+                (true, Key::LEFT) => NavigationAction::GoBack(),
+                (true, Key::RIGHT) => NavigationAction::GoForward(),
+                // Compare the output of render backends
+                (true, Key::DOWN) => NavigationAction::CycleBackend(),
+                (true, Key::UP) if key.modifiers.shift => NavigationAction::Capture(),
+                (true, Key::UP) => NavigationAction::Screenshot(),
                 _ => NavigationAction::Forward,
             },
             Some(cursor) => {
                 if let Some(url) = &mut self.url {
-                    // TODO: Unicode
+                    let length = url.chars().count();
+
                     match key.char {
                         // Return
-                        0x0d => return NavigationAction::GoTo(url.clone()),
-                        // Up
-                        0x11 => self.cursor = Some(0),
-                        // Down
-                        0x12 => self.cursor = Some(url.width()),
-                        // Right
-                        0x13 => self.cursor = Some((cursor + 1).min(url.width())),
-                        // Left
-                        0x14 => self.cursor = Some(if cursor > 0 { cursor - 1 } else { 0 }),
+                        '\r' => return NavigationAction::GoTo(url.clone()),
+                        Key::UP => self.cursor = Some(0),
+                        Key::DOWN => self.cursor = Some(length),
+                        Key::RIGHT => self.cursor = Some((cursor + 1).min(length)),
+                        Key::LEFT => self.cursor = Some(if cursor > 0 { cursor - 1 } else { 0 }),
                         // Backspace
-                        0x7f => {
-                            if cursor > 0 {
-                                url.remove(cursor - 1);
+                        '\x7f' if cursor > 0 => {
+                            url.remove(byte_index(url, cursor - 1));
 
-                                self.cursor = Some(cursor - 1);
-                            }
+                            self.cursor = Some(cursor - 1);
                         }
-                        key => {
-                            url.insert(cursor, key as char);
+                        char if key.is_text() => {
+                            url.insert(byte_index(url, cursor), char);
 
-                            self.cursor = Some((cursor + 1).min(url.width()))
+                            self.cursor = Some(cursor + 1)
                         }
+                        _ => (),
                     }
 
                     NavigationAction::Ignore
@@ -103,10 +122,6 @@ impl Navigation {
         }
     }
 
-    pub fn url_size(&self) -> usize {
-        self.display_url().width()
-    }
-
     pub fn mouse_up(&mut self, origin: Point) -> NavigationAction {
         if origin.y != 0 {
             self.cursor = None;
@@ -125,19 +140,19 @@ impl Navigation {
 
         self.cursor = None;
 
-        return match origin.x {
+        match origin.x {
             0..=2 => NavigationAction::GoBack(),
             3..=5 => NavigationAction::GoForward(),
             6..=8 => NavigationAction::Refresh(),
             11.. => {
-                self.cursor = Some(self.url_size().min(origin.x as usize - 11));
+                self.cursor = Some(char_index(self.display_url(), origin.x as usize - 11));
 
                 log::debug!("setting cursor to {:?}", self.cursor);
 
                 NavigationAction::Ignore
             }
             _ => NavigationAction::Ignore,
-        };
+        }
     }
     pub fn mouse_move(&mut self, _origin: Point) -> NavigationAction {
         NavigationAction::Forward
@@ -149,7 +164,7 @@ impl Navigation {
             (_, None) => true,
             (_, Some(current)) => current != url,
         } {
-            self.cursor = Some(url.len())
+            self.cursor = Some(url.chars().count())
         }
 
         self.url = Some(url.to_owned());
@@ -195,7 +210,8 @@ impl Navigation {
         } else {
             0
         };
-        let url: String = self.display_url().chars().take(space).collect();
+        let url = self.display_url();
+        let url = &url[..byte_index(url, char_index(url, space))];
         let width = url.width();
         let padded = format!(" {}{} ", url, " ".repeat(space - width));
         let mut elements = Vec::new();
@@ -210,7 +226,7 @@ impl Navigation {
             for element in list {
                 let width = element.text.width() as i32;
 
-                elements.push((point.clone(), element));
+                elements.push((point, element));
 
                 point = point + (width, 0);
             }
@@ -219,3 +235,74 @@ impl Navigation {
         elements
     }
 }
+
+/// Index of the byte starting the character at `index` in a string
+fn byte_index(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(index, _)| index)
+}
+
+/// Index of the character drawn over `column` in a string,
+/// or the number of characters past its end
+fn char_index(text: &str, column: usize) -> usize {
+    let mut width = 0;
+
+    text.chars()
+        .position(|char| {
+            width += char.width().unwrap_or(0);
+            width > column
+        })
+        .unwrap_or_else(|| text.chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(nav: &mut Navigation, keys: &[char]) {
+        for &key in keys {
+            nav.keypress(&key.into());
+        }
+    }
+
+    #[test]
+    fn edits_urls_with_wide_characters() {
+        let mut nav = Navigation::new();
+
+        nav.push("about:blank", false, false);
+        nav.mouse_down(Point::new(11, 0));
+        nav.push("https://例子.测试/页", false, false);
+
+        // The cursor is placed after the last character, in columns
+        assert_eq!(nav.cursor(), Some(Point::new(11 + 20, 0)));
+
+        type_keys(
+            &mut nav,
+            &['\x7f', Key::LEFT, Key::LEFT, Key::LEFT, '\x7f', '中'],
+        );
+
+        assert_eq!(nav.display_url(), "https://例子中测试/");
+        assert_eq!(nav.cursor(), Some(Point::new(11 + 14, 0)));
+
+        // Clicking on either column of a wide character places the cursor before it
+        nav.mouse_down(Point::new(11 + 12, 0));
+        assert_eq!(nav.cursor(), Some(Point::new(11 + 12, 0)));
+        nav.mouse_down(Point::new(11 + 13, 0));
+        assert_eq!(nav.cursor(), Some(Point::new(11 + 12, 0)));
+        nav.mouse_down(Point::new(11 + 40, 0));
+        assert_eq!(nav.cursor(), Some(Point::new(11 + 19, 0)));
+    }
+
+    #[test]
+    fn truncates_wide_urls_to_the_bar() {
+        let mut nav = Navigation::new();
+
+        nav.push(&"测".repeat(20), false, false);
+
+        let elements = nav.render(Size::new(23, 1));
+        let (_, url) = &elements[elements.len() - 2];
+
+        assert_eq!(url.text, format!(" {} ", "测".repeat(5)));
+    }
+}
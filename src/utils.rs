@@ -1,7 +1,10 @@
 mod four_bits;
 mod try_block;
 
+pub mod base64;
 pub mod log;
+pub mod png;
+pub mod terminfo;
 
 use try_block::*;
 
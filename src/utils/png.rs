@@ -0,0 +1,288 @@
+/// Encode RGB pixels as a PNG image.
+///
+/// Scanlines use the "sub" filter and are compressed with fixed Huffman codes.
+pub fn encode(rgb: &[u8], width: usize, height: usize, out: &mut Vec<u8>) {
+    let stride = width * 3;
+    let mut filtered = Vec::with_capacity((stride + 1) * height);
+
+    for row in rgb.chunks_exact(stride).take(height) {
+        // Filter type 1: difference with the pixel on the left
+        filtered.push(1);
+        filtered.extend_from_slice(&row[..3]);
+        filtered.extend(
+            row[3..]
+                .iter()
+                .zip(row.iter())
+                .map(|(pixel, left)| pixel.wrapping_sub(*left)),
+        );
+    }
+
+    let mut header = Vec::with_capacity(13);
+
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    out.extend_from_slice(b"\x89PNG\r\n\x1a\n");
+    write_chunk(out, b"IHDR", &header);
+    write_chunk(out, b"IDAT", &zlib(&filtered));
+    write_chunk(out, b"IEND", &[]);
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let start = out.len() + 4;
+
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+
+    let crc = crc32(&out[start..]);
+
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+
+        a %= 65521;
+        b %= 65521;
+    }
+
+    b << 16 | a
+}
+
+/// Compress data into a zlib stream using a single fixed Huffman block
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+
+    // Compression method 8 with a 32K window, fastest compression level
+    bits.out.extend_from_slice(&[0x78, 0x01]);
+    // Final block, fixed Huffman codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    deflate(data, &mut bits);
+
+    // End of block
+    bits.write_literal(256);
+    bits.flush();
+    bits.out.extend_from_slice(&adler32(data).to_be_bytes());
+    bits.out
+}
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+
+/// Greedy LZ77 compression using the last position of every 3 bytes hash
+fn deflate(data: &[u8], bits: &mut BitWriter) {
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let hash = |i: usize| {
+        let value = (data[i] as usize) << 16 | (data[i + 1] as usize) << 8 | data[i + 2] as usize;
+
+        (value.wrapping_mul(2654435761) >> 8) & ((1 << HASH_BITS) - 1)
+    };
+    let mut i = 0;
+
+    while i < data.len() {
+        let mut length = 0;
+        let mut distance = 0;
+
+        if i + MIN_MATCH <= data.len() {
+            let key = hash(i);
+            let candidate = head[key];
+
+            head[key] = i;
+
+            if candidate != usize::MAX && i - candidate <= WINDOW_SIZE {
+                let max = MAX_MATCH.min(data.len() - i);
+
+                while length < max && data[candidate + length] == data[i + length] {
+                    length += 1;
+                }
+
+                distance = i - candidate;
+            }
+        }
+
+        if length >= MIN_MATCH {
+            bits.write_length(length);
+            bits.write_distance(distance);
+
+            // Keep the hash table updated inside the match
+            for j in (i + 1)..(i + length).min(data.len().saturating_sub(MIN_MATCH - 1)) {
+                head[hash(j)] = j;
+            }
+
+            i += length;
+        } else {
+            bits.write_literal(data[i] as u16);
+            i += 1;
+        }
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Write `count` bits of `value`, least significant bit first
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, most significant bit first
+    fn write_code(&mut self, code: u32, count: u32) {
+        let reversed = code.reverse_bits() >> (32 - count);
+
+        self.write(reversed, count)
+    }
+
+    /// Write a symbol of the fixed literal/length alphabet
+    fn write_literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_length(&mut self, length: usize) {
+        let index = LENGTH_BASE
+            .iter()
+            .rposition(|&base| base as usize <= length)
+            .unwrap();
+
+        self.write_literal(257 + index as u16);
+        self.write(
+            (length - LENGTH_BASE[index] as usize) as u32,
+            LENGTH_EXTRA[index] as u32,
+        );
+    }
+
+    fn write_distance(&mut self, distance: usize) {
+        let index = DISTANCE_BASE
+            .iter()
+            .rposition(|&base| base as usize <= distance)
+            .unwrap();
+
+        self.write_code(index as u32, 5);
+        self.write(
+            (distance - DISTANCE_BASE[index] as usize) as u32,
+            DISTANCE_EXTRA[index] as u32,
+        );
+    }
+
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+            self.buffer = 0;
+            self.count = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Split a PNG image into the kind, data and CRC of its chunks
+    fn chunks(mut png: &[u8]) -> Vec<(&[u8], &[u8], u32)> {
+        let mut chunks = Vec::new();
+
+        png = png.strip_prefix(b"\x89PNG\r\n\x1a\n").unwrap();
+
+        while !png.is_empty() {
+            let length = u32::from_be_bytes(png[..4].try_into().unwrap()) as usize;
+            let (kind, data) = png[4..8 + length].split_at(4);
+            let crc = u32::from_be_bytes(png[8 + length..12 + length].try_into().unwrap());
+
+            chunks.push((kind, data, crc));
+            png = &png[12 + length..];
+        }
+
+        chunks
+    }
+
+    #[test]
+    fn computes_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+
+    #[test]
+    fn encodes_a_single_pixel() {
+        let mut png = Vec::new();
+
+        encode(&[255, 0, 0], 1, 1, &mut png);
+
+        let chunks = chunks(&png);
+        let kinds = chunks.iter().map(|(kind, ..)| *kind).collect::<Vec<_>>();
+
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].2, 0x907753de);
+        assert_eq!(chunks[2].2, 0xae426082);
+
+        let (_, data, crc) = chunks[1];
+
+        assert_eq!(crc, crc32(&[b"IDAT", data].concat()));
+        // Adler-32 of the filter type followed by the pixel
+        assert_eq!(data[data.len() - 4..], 0x03050101u32.to_be_bytes());
+    }
+}
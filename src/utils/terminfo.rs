@@ -0,0 +1,123 @@
+use std::{env, fs, path::PathBuf};
+
+/// Magic numbers of compiled terminfo files, using 16 or 32 bits numbers
+const MAGIC_16: i16 = 0o432;
+const MAGIC_32: i16 = 0o1036;
+
+/// Index of the `bce` boolean capability, set if erasing uses the current background color
+pub const BACK_COLOR_ERASE: usize = 28;
+/// Index of the `colors` numeric capability
+pub const COLORS: usize = 13;
+/// Index of the `rep` string capability, repeating a character
+pub const REPEAT_CHAR: usize = 121;
+
+/// Capabilities read from a compiled terminfo entry.
+pub struct Terminfo {
+    bools: Vec<bool>,
+    numbers: Vec<i32>,
+    strings: Vec<Option<Vec<u8>>>,
+}
+
+impl Terminfo {
+    /// Load the terminfo entry of a terminal
+    pub fn load(term: &str) -> Option<Terminfo> {
+        let data = find(term)?;
+        let header = |index: usize| -> Option<i16> {
+            let bytes = data.get(index * 2..index * 2 + 2)?;
+
+            Some(i16::from_le_bytes([bytes[0], bytes[1]]))
+        };
+        let width = match header(0)? {
+            MAGIC_16 => 2,
+            MAGIC_32 => 4,
+            _ => return None,
+        };
+        let (names, bools, numbers, strings, table) = (
+            header(1)? as usize,
+            header(2)? as usize,
+            header(3)? as usize,
+            header(4)? as usize,
+            header(5)? as usize,
+        );
+        let start = 12 + names;
+        let bools = data.get(start..start + bools)?;
+        // Numbers are aligned on an even byte boundary
+        let start = (start + bools.len()).next_multiple_of(2);
+        let numbers = data
+            .get(start..start + numbers * width)?
+            .chunks_exact(width)
+            .map(|bytes| match width {
+                2 => i16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+                _ => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            })
+            .collect::<Vec<_>>();
+        // Strings are offsets into a table of null-terminated strings, negative if absent
+        let start = start + numbers.len() * width;
+        let offsets = data.get(start..start + strings * 2)?;
+        let table = data.get(start + offsets.len()..start + offsets.len() + table)?;
+        let strings = offsets
+            .chunks_exact(2)
+            .map(|bytes| {
+                let offset = usize::try_from(i16::from_le_bytes([bytes[0], bytes[1]])).ok()?;
+                let string = table.get(offset..)?;
+                let end = string.iter().position(|&byte| byte == 0)?;
+
+                Some(string[..end].to_vec())
+            })
+            .collect();
+
+        Some(Terminfo {
+            bools: bools.iter().map(|&value| value == 1).collect(),
+            numbers,
+            strings,
+        })
+    }
+
+    /// Read a boolean capability
+    pub fn flag(&self, index: usize) -> bool {
+        self.bools.get(index).copied().unwrap_or(false)
+    }
+
+    /// Read a numeric capability, returns -1 if absent
+    pub fn number(&self, index: usize) -> i32 {
+        self.numbers.get(index).copied().unwrap_or(-1)
+    }
+
+    /// Read a string capability
+    pub fn string(&self, index: usize) -> Option<&[u8]> {
+        self.strings.get(index)?.as_deref()
+    }
+}
+
+/// Find the compiled terminfo entry of a terminal in the usual directories
+fn find(term: &str) -> Option<Vec<u8>> {
+    let first = term.chars().next()?;
+    let mut dirs = Vec::new();
+
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+
+    if let Ok(list) = env::var("TERMINFO_DIRS") {
+        dirs.extend(
+            list.split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        );
+    }
+
+    for dir in ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"] {
+        dirs.push(PathBuf::from(dir));
+    }
+
+    dirs.iter().find_map(|dir| {
+        // Entries are stored by first letter, or its hex code on case-insensitive systems
+        fs::read(dir.join(first.to_string()).join(term))
+            .or_else(|_| fs::read(dir.join(format!("{:x}", first as u32)).join(term)))
+            .ok()
+    })
+}